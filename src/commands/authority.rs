@@ -0,0 +1,206 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+
+pub static AUTH_MSG_ID: &str = "AUTH";
+pub static OWNER_MSG_ID: &str = "OWNER";
+
+// API Call : AUTH
+//
+// Every object is owned by the player who added it. Only the owner and the
+// host of the game session can modify it.
+//
+// A player asks for the authority over an object with a Request. The server
+// forwards the request to the owner of the object, with the player field set
+// to the requesting player, or to the host when the owner is not connected.
+//
+// The owner or the host gives the object to a player with a Grant, for example
+// when a player boards another ship. The server answers with a STATUS message.
+//
+// API Push : OWNER
+//
+// Sent to every member of the game session when the owner of an object
+// changes, either after a Grant or when the owner left the session.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AuthorityOperation {
+    Request,
+    Grant
+}
+
+fn u64_to_authority_operation(value: u64) -> Result<AuthorityOperation, String> {
+    match value {
+        0 => Ok(AuthorityOperation::Request),
+        1 => Ok(AuthorityOperation::Grant),
+        _ => Err(format!("Unknown authority operation {}", value))
+    }
+}
+
+fn authority_operation_to_u64(operation: AuthorityOperation) -> u64 {
+    match operation {
+        AuthorityOperation::Request => 0,
+        AuthorityOperation::Grant => 1
+    }
+}
+
+/**
+ * What happens to the objects of a player when the player leaves the game
+ * session. Chosen when the object is added.
+ */
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum OwnerLeavePolicy {
+    RevertToHost,
+    Delete
+}
+
+pub fn u64_to_owner_leave_policy(value: u64) -> Result<OwnerLeavePolicy, String> {
+    match value {
+        0 => Ok(OwnerLeavePolicy::RevertToHost),
+        1 => Ok(OwnerLeavePolicy::Delete),
+        _ => Err(format!("Unknown owner leave policy {}", value))
+    }
+}
+
+pub fn owner_leave_policy_to_u64(policy: OwnerLeavePolicy) -> u64 {
+    match policy {
+        OwnerLeavePolicy::RevertToHost => 0,
+        OwnerLeavePolicy::Delete => 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AuthorityCommand {
+    pub id: String,
+    pub operation: AuthorityOperation, // Encoded as 8 bytes
+    pub object_id: u64,
+    pub player: String
+}
+
+impl AuthorityCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<AuthorityCommand, String> {
+        AuthorityCommand::deserialize(data)
+    }
+
+    pub fn request(object_id: u64, player: &str) -> AuthorityCommand {
+        AuthorityCommand {
+            id: String::from(AUTH_MSG_ID),
+            operation: AuthorityOperation::Request,
+            object_id,
+            player: String::from(player)
+        }
+    }
+
+    pub fn grant(object_id: u64, player: &str) -> AuthorityCommand {
+        AuthorityCommand {
+            id: String::from(AUTH_MSG_ID),
+            operation: AuthorityOperation::Grant,
+            object_id,
+            player: String::from(player)
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<AuthorityCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = AuthorityCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            operation: u64_to_authority_operation(read_u64_field(&mut reader)?)?,
+            object_id: read_u64_field(&mut reader)?,
+            player: read_padded_string(&mut reader, 32)?
+        };
+
+        if res.id != AUTH_MSG_ID {
+            return Err(format!("AuthorityCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the AuthorityCommand instance.
+     *
+     * Wire format : [id:8][operation:8][object_id:8][player:32]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(authority_operation_to_u64(self.operation)),
+            u64_to_buf(self.object_id),
+            pad_string(self.player.as_bytes(), 32)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OwnerMessage {
+    pub id: String,
+    pub object_id: u64,
+    pub owner: String
+}
+
+impl OwnerMessage {
+    pub fn from_info(object_id: u64, owner: &str) -> OwnerMessage {
+        OwnerMessage {
+            id: String::from(OWNER_MSG_ID),
+            object_id,
+            owner: String::from(owner)
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<OwnerMessage, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = OwnerMessage {
+            id: read_padded_string(&mut reader, 8)?,
+            object_id: read_u64_field(&mut reader)?,
+            owner: read_padded_string(&mut reader, 32)?
+        };
+
+        if res.id != OWNER_MSG_ID {
+            return Err(format!("OwnerMessage has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the OwnerMessage instance.
+     *
+     * Wire format : [id:8][object_id:8][owner:32]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.object_id),
+            pad_string(self.owner.as_bytes(), 32)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authority_roundtrip() {
+        let cmd = AuthorityCommand::grant(12, "Blackbeard");
+        let bytes = cmd.serialize();
+
+        assert_eq!(&bytes[8..16], u64_to_buf(1).as_slice());
+        assert_eq!(AuthorityCommand::deserialize(&bytes).unwrap(), cmd);
+
+        let cmd = AuthorityCommand::request(12, "");
+        assert_eq!(AuthorityCommand::deserialize(&cmd.serialize()).unwrap(), cmd);
+    }
+
+    #[test]
+    fn test_owner_roundtrip() {
+        let msg = OwnerMessage::from_info(12, "Anne Bonny");
+
+        assert_eq!(OwnerMessage::deserialize(&msg.serialize()).unwrap(), msg);
+    }
+}
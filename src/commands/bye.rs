@@ -15,6 +15,6 @@ impl ByeCommand {
         let created = ByeCommand {
             id: String::from("BYYE")
         };
-        return Ok(created);
+        Ok(created)
     }
 }
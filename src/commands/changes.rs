@@ -0,0 +1,153 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use crate::commands::getobj::ObjectEntry;
+
+pub static CHANGES_MSG_ID: &str = "CHANGES";
+
+// API Call : CHANGES
+//
+// Fetch only the objects created, updated or deleted in the game session
+// since the last change sequence number the client saw. Updated objects only
+// contain the properties that changed and deleted objects are sent with the
+// Deleted status.
+//
+// The reply contains the sequence number of the last change it includes, to
+// be sent with the next CHANGES request. When the client is too far behind
+// the server sends every object instead and sets the reset flag, the client
+// must then replace its whole state with the reply.
+
+#[derive(Debug, PartialEq)]
+pub struct ChangesCommand {
+    pub id: String,
+    pub since: u64
+}
+
+impl ChangesCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<ChangesCommand, String> {
+        ChangesCommand::deserialize(data)
+    }
+
+    pub fn from_info(since: u64) -> ChangesCommand {
+        ChangesCommand {
+            id: String::from(CHANGES_MSG_ID),
+            since
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<ChangesCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = ChangesCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            since: read_u64_field(&mut reader)?
+        };
+
+        if res.id != CHANGES_MSG_ID {
+            return Err(format!("ChangesCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][since:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.since)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChangesReply {
+    pub id: String,
+    pub sequence: u64,
+    pub reset: bool, // Encoded as 8 bytes
+    pub objects: Vec<ObjectEntry>
+}
+
+impl ChangesReply {
+    pub fn from_info(sequence: u64, reset: bool, objects: Vec<ObjectEntry>) -> ChangesReply {
+        ChangesReply {
+            id: String::from(CHANGES_MSG_ID),
+            sequence,
+            reset,
+            objects
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<ChangesReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let sequence = read_u64_field(&mut reader)?;
+        let reset = read_u64_field(&mut reader)? != 0;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut objects = vec![];
+        for _i in 0..count {
+            objects.push(ObjectEntry::try_from(&mut reader)?);
+        }
+
+        Ok(ChangesReply {
+            id,
+            sequence,
+            reset,
+            objects
+        })
+    }
+
+    /**
+     * Wire format : [id:8][sequence:8][reset:8][objects count:8][objects...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.sequence),
+            u64_to_buf(self.reset as u64),
+            u64_to_buf(self.objects.len() as u64)
+        ];
+
+        for object in &self.objects {
+            result.push(object.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::getobj::EntryStatus;
+
+    #[test]
+    fn test_changes_roundtrip() {
+        let cmd = ChangesCommand::from_info(42);
+
+        let output = ChangesCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_changes_reply_roundtrip() {
+        let deleted = ObjectEntry {
+            status: EntryStatus::Deleted,
+            object_id: 3,
+            version: 5,
+            parent_id: 0,
+            name: String::from("Ship3"),
+            properties: vec![]
+        };
+
+        let reply = ChangesReply::from_info(57, false, vec![deleted]);
+
+        let output = ChangesReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+    }
+}
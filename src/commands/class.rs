@@ -0,0 +1,248 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use super::values::*;
+
+pub static CLASS_MSG_ID: &str = "CLASS";
+
+// API Call : CLASS
+//
+// Sent by the host of a game session to register an object class, such as
+// "Ship" or "Island", and the properties objects of the class can have.
+// Registering a class again replaces its schema.
+//
+// Each property has a compact id, the 8 bytes name used in PUTOBJ, and a
+// longer logical name that GETOBJ also accepts. Objects added with a class are
+// validated against its schema : unknown properties, data that is not a value
+// of the property type, sizes out of bounds and missing required properties
+// are rejected with an Invalid status.
+//
+// Property types are not sent with the properties, clients encode and decode
+// the data with the type declared here. Properties of objects without a class
+// are raw data the server does not look at.
+//
+// Properties often used in QUERY filters can be indexed. A Hash index serves
+// Equal filters, an Ordered index also serves Less and Greater filters and is
+// only allowed on Int, Float and Str properties. Indexes are kept up to date
+// on every change, queries on the class use them automatically.
+//
+// Each property also has a visibility. Public properties are sent to every
+// member of the session. Owner properties are only sent to the owner of the
+// object and Team properties to the players on the team of the owner, see
+// TEAM. The host receives every property. Properties of objects without a
+// class are public.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum IndexKind {
+    None,
+    Hash,
+    Ordered
+}
+
+fn u64_to_index_kind(value: u64) -> Result<IndexKind, String> {
+    match value {
+        0 => Ok(IndexKind::None),
+        1 => Ok(IndexKind::Hash),
+        2 => Ok(IndexKind::Ordered),
+        _ => Err(format!("Unknown index kind {}", value))
+    }
+}
+
+fn index_kind_to_u64(kind: IndexKind) -> u64 {
+    match kind {
+        IndexKind::None => 0,
+        IndexKind::Hash => 1,
+        IndexKind::Ordered => 2
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Visibility {
+    Public,
+    Owner,
+    Team
+}
+
+fn u64_to_visibility(value: u64) -> Result<Visibility, String> {
+    match value {
+        0 => Ok(Visibility::Public),
+        1 => Ok(Visibility::Owner),
+        2 => Ok(Visibility::Team),
+        _ => Err(format!("Unknown visibility {}", value))
+    }
+}
+
+fn visibility_to_u64(visibility: Visibility) -> u64 {
+    match visibility {
+        Visibility::Public => 0,
+        Visibility::Owner => 1,
+        Visibility::Team => 2
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PropertySchema {
+    pub property_id: String,
+    pub name: String,
+    pub value_type: ValueType,
+    pub min_size: u64,
+    pub max_size: u64, // 0 means no limit
+    pub required: bool,
+    pub index: IndexKind, // Encoded as 8 bytes
+    pub visibility: Visibility // Encoded as 8 bytes
+}
+
+impl PropertySchema {
+
+    /**
+     * Serialize the structure to a binary vector.
+     *
+     * Wire format : [property_id:8][name:32][type:8][min_size:8]
+     *               [max_size:8][required:8][index:8][visibility:8]
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.property_id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            u64_to_buf(value_type_to_u32(self.value_type) as u64),
+            u64_to_buf(self.min_size),
+            u64_to_buf(self.max_size),
+            u64_to_buf(self.required as u64),
+            u64_to_buf(index_kind_to_u64(self.index)),
+            u64_to_buf(visibility_to_u64(self.visibility))
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+impl TryFrom::<&mut BufReader<&[u8]>> for PropertySchema {
+    type Error = String;
+
+    fn try_from(reader: &mut BufReader<&[u8]>) -> Result<Self, Self::Error> {
+        let property_id = read_padded_string(reader, 8)?;
+        let name = read_padded_string(reader, 32)?;
+        let value_type = read_u64_field(reader)?;
+
+        let result = PropertySchema {
+            property_id,
+            name,
+            value_type: u32_to_value_type(value_type.try_into().map_err(|_| "Invalid value type")?)?,
+            min_size: read_u64_field(reader)?,
+            max_size: read_u64_field(reader)?,
+            required: read_u64_field(reader)? != 0,
+            index: u64_to_index_kind(read_u64_field(reader)?)?,
+            visibility: u64_to_visibility(read_u64_field(reader)?)?
+        };
+
+        Ok(result)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ClassCommand {
+    pub id: String,
+    pub name: String,
+    pub properties: Vec<PropertySchema>
+}
+
+impl ClassCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<ClassCommand, String> {
+        ClassCommand::deserialize(data)
+    }
+
+    /**
+     * Create a ClassCommand with the information provided.
+     */
+    pub fn from_info(name: &str, properties: Vec<PropertySchema>) -> ClassCommand {
+        ClassCommand {
+            id: String::from(CLASS_MSG_ID),
+            name: String::from(name),
+            properties
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a ClassCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<ClassCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let name = read_padded_string(&mut reader, 32)?;
+
+        let count = read_u64_field(&mut reader)?;
+
+        let mut properties = vec![];
+        for _i in 0..count {
+            properties.push(PropertySchema::try_from(&mut reader)?);
+        }
+
+        if id != CLASS_MSG_ID {
+            return Err(format!("ClassCommand has invalid ID [{}]", id));
+        }
+
+        Ok(ClassCommand { id, name, properties })
+    }
+
+    /**
+     * Serialize the ClassCommand instance.
+     *
+     * Wire format : [id:8][name:32][properties count:8][properties...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for property in &self.properties {
+            result.push(property.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_roundtrip() {
+        let properties = vec![
+            PropertySchema {
+                property_id: String::from("Pos"),
+                name: String::from("Position"),
+                value_type: ValueType::Vec2,
+                min_size: 0,
+                max_size: 0,
+                required: true,
+                index: IndexKind::None,
+                visibility: Visibility::Public
+            },
+            PropertySchema {
+                property_id: String::from("Crew"),
+                name: String::from("CrewMembers"),
+                value_type: ValueType::List,
+                min_size: 4,
+                max_size: 1024,
+                required: false,
+                index: IndexKind::Hash,
+                visibility: Visibility::Team
+            }
+        ];
+
+        let cmd = ClassCommand::from_info("Ship", properties);
+        let bytes = cmd.serialize();
+
+        // Each property schema takes 88 bytes.
+        assert_eq!(bytes.len(), 48 + 2 * 88);
+
+        let output = ClassCommand::deserialize(&bytes).unwrap();
+        assert_eq!(cmd, output);
+
+        ClassCommand::deserialize(&bytes[0..bytes.len() - 8]).expect_err("Buffer is truncated");
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static CREATE_MSG_ID: &str = "CREATE";
+
+// Longest spectator delay, in seconds.
+pub static MAX_SPECTATOR_DELAY: u64 = 3600;
+
+// Longest history, in milliseconds.
+pub static MAX_HISTORY_MS: u64 = 10 * 60 * 1000;
+
+// API Call : CREATE
+//
+// Start a new game session that can be joined by other clients. The client
+// creating the session becomes its host. An empty password means anyone can
+// join the session.
+//
+// A max_players of 0 means the session has no capacity limit. Spectators do
+// not count toward that limit. The spectator delay is the number of seconds
+// object updates and replies to object reads are held back before being sent
+// to spectators, at most MAX_SPECTATOR_DELAY.
+//
+// The history is the number of milliseconds of past object states kept by the
+// server for REWIND queries, 0 keeps no history, at most MAX_HISTORY_MS.
+
+#[derive(Debug, PartialEq)]
+pub struct CreateCommand {
+    pub id: String,
+    pub name: String,
+    pub password: String,
+    pub max_players: u64,
+    pub spectator_delay: u64,
+    pub history_ms: u64
+}
+
+impl CreateCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<CreateCommand, String> {
+        CreateCommand::deserialize(data)
+    }
+
+    /**
+     * Create a CreateCommand with the information provided.
+     */
+    pub fn from_info(name: &str, password: &str, max_players: u64, spectator_delay: u64) -> CreateCommand {
+        CreateCommand {
+            id: String::from(CREATE_MSG_ID),
+            name: String::from(name),
+            password: String::from(password),
+            max_players,
+            spectator_delay,
+            history_ms: 0
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a CreateCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<CreateCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = CreateCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            name: read_padded_string(&mut reader, 32)?,
+            password: read_padded_string(&mut reader, 32)?,
+            max_players: read_u64_field(&mut reader)?,
+            spectator_delay: read_u64_field(&mut reader)?,
+            history_ms: read_u64_field(&mut reader)?
+        };
+
+        validate_command(&res)?;
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the CreateCommand instance.
+     *
+     * Wire format : [id:8][name:32][password:32][max_players:8]
+     *               [spectator_delay:8][history_ms:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            pad_string(self.password.as_bytes(), 32),
+            u64_to_buf(self.max_players),
+            u64_to_buf(self.spectator_delay),
+            u64_to_buf(self.history_ms)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+fn validate_command(command: &CreateCommand) -> Result<(), String> {
+    if command.id != CREATE_MSG_ID {
+        return Err(format!("CreateCommand has invalid ID [{}]", command.id));
+    }
+
+    if command.name.is_empty() {
+        return Err(String::from("CreateCommand has an empty session name"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_roundtrip() {
+        let mut cmd = CreateCommand::from_info("Caribbean", "Rum", 8, 30);
+        cmd.history_ms = 2000;
+
+        let bytes = cmd.serialize();
+        assert_eq!(bytes.len(), 96);
+
+        let output = CreateCommand::deserialize(&bytes).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_create_validation() {
+        let cmd = CreateCommand::from_info("", "", 0, 0);
+
+        CreateCommand::deserialize(&cmd.serialize()).expect_err("Session name is empty");
+    }
+}
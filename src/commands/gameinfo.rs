@@ -0,0 +1,153 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+use super::phase::*;
+
+pub static GAMEINFO_MSG_ID: &str = "GAMEINFO";
+
+// API Call : GAMEINFO
+//
+// Get info about a particular game session running. The server answers with
+// a GAMEINFO reply listing the players and the spectators of the session
+// separately, along with its phase and start timestamp, see PHASE.
+
+#[derive(Debug, PartialEq)]
+pub struct GameInfoCommand {
+    pub id: String,
+    pub name: String
+}
+
+impl GameInfoCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<GameInfoCommand, String> {
+        GameInfoCommand::deserialize(data)
+    }
+
+    pub fn from_info(name: &str) -> GameInfoCommand {
+        GameInfoCommand {
+            id: String::from(GAMEINFO_MSG_ID),
+            name: String::from(name)
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a GameInfoCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<GameInfoCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = GameInfoCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            name: read_padded_string(&mut reader, 32)?
+        };
+
+        if res.id != GAMEINFO_MSG_ID {
+            return Err(format!("GameInfoCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the GameInfoCommand instance.
+     *
+     * Wire format : [id:8][name:32]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GameInfoReply {
+    pub id: String,
+    pub name: String,
+    pub host: String,
+    pub max_players: u64,
+    pub spectator_delay: u64,
+    pub phase: GamePhase, // Encoded as 8 bytes
+    pub start_at: u64,
+    pub players: Vec<String>,
+    pub spectators: Vec<String>
+}
+
+impl GameInfoReply {
+    /**
+     * Deserialize a buffer into a GameInfoReply instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<GameInfoReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = GameInfoReply {
+            id: read_padded_string(&mut reader, 8)?,
+            name: read_padded_string(&mut reader, 32)?,
+            host: read_padded_string(&mut reader, 32)?,
+            max_players: read_u64_field(&mut reader)?,
+            spectator_delay: read_u64_field(&mut reader)?,
+            phase: u64_to_game_phase(read_u64_field(&mut reader)?)?,
+            start_at: read_u64_field(&mut reader)?,
+            players: read_string_list(&mut reader, 32)?,
+            spectators: read_string_list(&mut reader, 32)?
+        };
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the GameInfoReply instance.
+     *
+     * Wire format : [id:8][name:32][host:32][max_players:8]
+     *               [spectator_delay:8][phase:8][start_at:8]
+     *               [players count:8][player:32]...
+     *               [spectators count:8][spectator:32]...
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            pad_string(self.host.as_bytes(), 32),
+            u64_to_buf(self.max_players),
+            u64_to_buf(self.spectator_delay),
+            u64_to_buf(game_phase_to_u64(self.phase)),
+            u64_to_buf(self.start_at),
+            string_list_to_buf(&self.players, 32),
+            string_list_to_buf(&self.spectators, 32)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gameinfo_roundtrip() {
+        let cmd = GameInfoCommand::from_info("Caribbean");
+
+        let output = GameInfoCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_gameinfo_reply_roundtrip() {
+        let reply = GameInfoReply {
+            id: String::from(GAMEINFO_MSG_ID),
+            name: String::from("Caribbean"),
+            host: String::from("Host"),
+            max_players: 4,
+            spectator_delay: 30,
+            phase: GamePhase::Countdown,
+            start_at: 1_600_000_000_000,
+            players: vec![String::from("Host"), String::from("Player")],
+            spectators: vec![String::from("Watcher")]
+        };
+
+        let output = GameInfoReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+    }
+}
@@ -0,0 +1,268 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use crate::commands::putobj::ObjProperties;
+
+pub static GETOBJ_MSG_ID: &str = "GETOBJ";
+
+// API Call : GETOBJ
+//
+// Query object properties. Sends a list of objects that the client wants to
+// get the properties of, and optionally the names of the properties wanted.
+// An empty property list returns every property of the objects.
+//
+// The server answers with a GETOBJ reply holding one entry per requested
+// object, in the same order. Objects that do not exist are marked NotFound
+// and have no properties.
+//
+// Objects can have a parent object, for example the island a building sits
+// on. When `include_children` is set, each requested object is followed by
+// all its descendants, parents before their children.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum EntryStatus {
+    Found,
+    NotFound,
+    Deleted,
+    Entered,
+    Left
+}
+
+fn u64_to_entry_status(value: u64) -> Result<EntryStatus, String> {
+    match value {
+        0 => Ok(EntryStatus::Found),
+        1 => Ok(EntryStatus::NotFound),
+        2 => Ok(EntryStatus::Deleted),
+        3 => Ok(EntryStatus::Entered),
+        4 => Ok(EntryStatus::Left),
+        _ => Err(format!("Unknown entry status {}", value))
+    }
+}
+
+fn entry_status_to_u64(status: EntryStatus) -> u64 {
+    match status {
+        EntryStatus::Found => 0,
+        EntryStatus::NotFound => 1,
+        EntryStatus::Deleted => 2,
+        EntryStatus::Entered => 3,
+        EntryStatus::Left => 4
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GetObjCommand {
+    pub id: String,
+    pub object_names: Vec<String>,
+    pub property_names: Vec<String>,
+    pub include_children: bool // Encoded as 8 bytes
+}
+
+impl GetObjCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<GetObjCommand, String> {
+        GetObjCommand::deserialize(data)
+    }
+
+    pub fn from_info(object_names: &[&str], property_names: &[&str]) -> GetObjCommand {
+        GetObjCommand {
+            id: String::from(GETOBJ_MSG_ID),
+            object_names: object_names.iter().map(|n| String::from(*n)).collect(),
+            property_names: property_names.iter().map(|n| String::from(*n)).collect(),
+            include_children: false
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<GetObjCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = GetObjCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            object_names: read_string_list(&mut reader, 32)?,
+            property_names: read_string_list(&mut reader, 8)?,
+            include_children: read_u64_field(&mut reader)? != 0
+        };
+
+        if res.id != GETOBJ_MSG_ID {
+            return Err(format!("GetObjCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][objects count:8][object name:32]...
+     *               [properties count:8][property name:8]...
+     *               [include_children:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            string_list_to_buf(&self.object_names, 32),
+            string_list_to_buf(&self.property_names, 8),
+            u64_to_buf(self.include_children as u64)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * The state of one object sent to a client.
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectEntry {
+    pub status: EntryStatus, // Encoded as 8 bytes
+    pub object_id: u64,
+    pub version: u64,
+    pub parent_id: u64, // 0 when the object has no parent
+    pub name: String,
+    pub properties: Vec<ObjProperties>
+}
+
+impl ObjectEntry {
+    pub fn not_found(name: &str) -> ObjectEntry {
+        ObjectEntry {
+            status: EntryStatus::NotFound,
+            object_id: 0,
+            version: 0,
+            parent_id: 0,
+            name: String::from(name),
+            properties: vec![]
+        }
+    }
+
+    /**
+     * Wire format : [status:8][object_id:8][version:8][parent_id:8]
+     *               [name:32][properties count:8][properties...]
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            u64_to_buf(entry_status_to_u64(self.status)),
+            u64_to_buf(self.object_id),
+            u64_to_buf(self.version),
+            u64_to_buf(self.parent_id),
+            pad_string(self.name.as_bytes(), 32),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for prop in &self.properties {
+            result.push(prop.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+impl TryFrom::<&mut BufReader<&[u8]>> for ObjectEntry {
+    type Error = String;
+
+    fn try_from(reader: &mut BufReader<&[u8]>) -> Result<Self, Self::Error> {
+        let status = u64_to_entry_status(read_u64_field(reader)?)?;
+        let object_id = read_u64_field(reader)?;
+        let version = read_u64_field(reader)?;
+        let parent_id = read_u64_field(reader)?;
+        let name = read_padded_string(reader, 32)?;
+
+        let prop_len = read_u64_field(reader)?;
+
+        let mut properties = vec![];
+        for _i in 0..prop_len {
+            properties.push(ObjProperties::try_from(&mut *reader)?);
+        }
+
+        Ok(ObjectEntry {
+            status,
+            object_id,
+            version,
+            parent_id,
+            name,
+            properties
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GetObjReply {
+    pub id: String,
+    pub objects: Vec<ObjectEntry>
+}
+
+impl GetObjReply {
+    pub fn from_info(objects: Vec<ObjectEntry>) -> GetObjReply {
+        GetObjReply::for_request(GETOBJ_MSG_ID, objects)
+    }
+
+    /**
+     * Create a reply with the GETOBJ layout answering another request.
+     */
+    pub fn for_request(request: &str, objects: Vec<ObjectEntry>) -> GetObjReply {
+        GetObjReply {
+            id: String::from(request),
+            objects
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<GetObjReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut objects = vec![];
+        for _i in 0..count {
+            objects.push(ObjectEntry::try_from(&mut reader)?);
+        }
+
+        Ok(GetObjReply {
+            id,
+            objects
+        })
+    }
+
+    /**
+     * Wire format : [id:8][objects count:8][objects...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.objects.len() as u64)
+        ];
+
+        for object in &self.objects {
+            result.push(object.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_getobj_roundtrip() {
+        let mut cmd = GetObjCommand::from_info(&["Ship1", "Island7"], &["Position"]);
+        cmd.include_children = true;
+
+        let output = GetObjCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_getobj_reply_roundtrip() {
+        let found = ObjectEntry {
+            status: EntryStatus::Found,
+            object_id: 1,
+            version: 4,
+            parent_id: 9,
+            name: String::from("Ship1"),
+            properties: vec![ObjProperties::raw("Position", b"1,1")]
+        };
+
+        let reply = GetObjReply::from_info(vec![found, ObjectEntry::not_found("Island7")]);
+
+        let output = GetObjReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+        assert_eq!(output.objects[1].status, EntryStatus::NotFound);
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static GRANT_MSG_ID: &str = "GRANT";
+
+// API Call : GRANT
+//
+// Sent by the host of a game session to allow another player of the session
+// to write a specific object, for example the ship controlled by that player.
+
+#[derive(Debug, PartialEq)]
+pub struct GrantCommand {
+    pub id: String,
+    pub player: String,
+    pub object_id: u64
+}
+
+impl GrantCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<GrantCommand, String> {
+        GrantCommand::deserialize(data)
+    }
+
+    /**
+     * Create a GrantCommand with the information provided.
+     */
+    pub fn from_info(player: &str, object_id: u64) -> GrantCommand {
+        GrantCommand {
+            id: String::from(GRANT_MSG_ID),
+            player: String::from(player),
+            object_id
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a GrantCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<GrantCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = GrantCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            player: read_padded_string(&mut reader, 32)?,
+            object_id: read_u64_field(&mut reader)?
+        };
+
+        if res.id != GRANT_MSG_ID {
+            return Err(format!("GrantCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the GrantCommand instance.
+     *
+     * Wire format : [id:8][player:32][object_id:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.player.as_bytes(), 32),
+            u64_to_buf(self.object_id)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_roundtrip() {
+        let cmd = GrantCommand::from_info("Blackbeard", 42);
+
+        let bytes = cmd.serialize();
+        assert_eq!(&bytes[40..48], u64_to_buf(42).as_slice());
+
+        let output = GrantCommand::deserialize(&bytes).unwrap();
+        assert_eq!(cmd, output);
+    }
+}
@@ -61,14 +61,14 @@ impl HelloCommand {
         reader.read_to_end(&mut msg_bytes).map_err(|_| "Unable to read message content from buffer.")?;
 
         let res = HelloCommand {
-            id: String::from_utf8(trim_vec_end(&id_bytes)).unwrap(),
-            user: String::from_utf8(trim_vec_end(&user_bytes)).unwrap(),
-            msg: String::from_utf8(trim_vec_end(&msg_bytes)).unwrap()
+            id: String::from_utf8(trim_vec_end(&id_bytes)).map_err(|_| "Message id is not UTF8.")?,
+            user: String::from_utf8(trim_vec_end(&user_bytes)).map_err(|_| "Message user is not UTF8.")?,
+            msg: String::from_utf8(trim_vec_end(&msg_bytes)).map_err(|_| "Message content is not UTF8.")?
         };
 
         validate_command(&res)?;
 
-        return Ok(res);
+        Ok(res)
     }
 
     /**
@@ -78,15 +78,15 @@ impl HelloCommand {
      * of the server protocol.
      */
     pub fn serialize(&self) -> Vec<u8> {
-        let mut result: Vec<Vec<u8>> = vec![];
-
-        result.push(pad_string(self.id.as_bytes(), 8));
-        result.push(pad_string(self.user.as_bytes(), 32));
-        result.push(self.msg.as_bytes().to_vec());
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.user.as_bytes(), 32),
+            self.msg.as_bytes().to_vec()
+        ];
 
         let flat: Vec<u8> = result.into_iter().flatten().collect();
 
-        return flat;
+        flat
     }
 }
 
@@ -98,13 +98,13 @@ fn validate_command(command: &HelloCommand) -> Result<(), String>
         return Err(error);
     }
 
-    if command.user.is_ascii() == false {
+    if !command.user.is_ascii() {
         let error = format!("HelloCommand has a username that's not entirely in ASCII [{}]", command.user);
 
         return Err(error);
     }
 
-    if command.msg.is_ascii() == false {
+    if !command.msg.is_ascii() {
         let error = format!("HelloCommand has a message that's not entirely in ASCII [{}]", command.msg);
 
         return Err(error);
@@ -217,4 +217,20 @@ mod tests {
         test.msg = String::from("HIDDEN MESSAGE\u{03A8}");
         validate_command(&test).expect_err("Message contains non ASCII characters.");
     }
+
+    /**
+     * Tests that deserializing bytes that are not UTF8 returns an error
+     * instead of panicking.
+     */
+    #[test]
+    fn test_hello_deserialize_invalid_utf8() {
+        let valid = HelloCommand::from_info("TestUsername", "Super Message").serialize();
+
+        for range in [0..2, 8..10, 40..42] {
+            let mut cmd = valid.clone();
+            cmd.splice(range, [0xC3, 0x28]);
+
+            HelloCommand::deserialize(&cmd).expect_err("Bytes are not UTF8");
+        }
+    }
 }
\ No newline at end of file
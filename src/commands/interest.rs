@@ -0,0 +1,92 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static INTEREST_MSG_ID: &str = "INTEREST";
+
+// API Call : INTEREST
+//
+// Set the area of the world the client cares about. Once set, NOTIFY and
+// CHANGES only deliver the objects inside the area, along with the objects
+// that have no position. Objects moving in the area are sent with the Entered
+// status and objects moving out of it with the Left status.
+//
+// The server answers with a reply using the GETOBJ layout, listing the objects
+// that entered or left the area because the area itself changed. Sending the
+// command with `enabled` unset removes the area.
+
+#[derive(Debug, PartialEq)]
+pub struct InterestCommand {
+    pub id: String,
+    pub enabled: bool, // Encoded as 8 bytes
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64
+}
+
+impl InterestCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<InterestCommand, String> {
+        InterestCommand::deserialize(data)
+    }
+
+    pub fn from_info(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> InterestCommand {
+        InterestCommand {
+            id: String::from(INTEREST_MSG_ID),
+            enabled: true,
+            min_x,
+            min_y,
+            max_x,
+            max_y
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<InterestCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = InterestCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            enabled: read_u64_field(&mut reader)? != 0,
+            min_x: read_f64_field(&mut reader)?,
+            min_y: read_f64_field(&mut reader)?,
+            max_x: read_f64_field(&mut reader)?,
+            max_y: read_f64_field(&mut reader)?
+        };
+
+        if res.id != INTEREST_MSG_ID {
+            return Err(format!("InterestCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][enabled:8][min_x:8][min_y:8][max_x:8][max_y:8]
+     *
+     * Coordinates are encoded as little endian f64.
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.enabled as u64),
+            f64_to_buf(self.min_x),
+            f64_to_buf(self.min_y),
+            f64_to_buf(self.max_x),
+            f64_to_buf(self.max_y)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interest_roundtrip() {
+        let cmd = InterestCommand::from_info(-10.0, -20.5, 300.0, 400.25);
+
+        let output = InterestCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+}
@@ -0,0 +1,186 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static INVITE_MSG_ID: &str = "INVITE";
+
+// API Call : INVITE
+//
+// Sent by the host of a game session to create or revoke an invite code. An
+// invite code can be given to JOIN in place of the session name and
+// password. Codes expire after `lifetime` seconds and can be limited to a
+// number of uses, 0 meaning unlimited. Lifetimes above MAX_INVITE_LIFETIME
+// are rejected with an Invalid status.
+//
+// When creating a code the `code` field is ignored, the server answers with an
+// INVITE reply containing the generated code.
+
+// Longest lifetime of an invite code, in seconds : 30 days.
+pub static MAX_INVITE_LIFETIME: u64 = 30 * 24 * 3600;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InviteOperation {
+    Create,
+    Revoke
+}
+
+fn u64_to_invite_operation(value: u64) -> Result<InviteOperation, String> {
+    match value {
+        0 => Ok(InviteOperation::Create),
+        1 => Ok(InviteOperation::Revoke),
+        _ => Err(format!("Unknown invite operation {}", value))
+    }
+}
+
+fn invite_operation_to_u64(operation: InviteOperation) -> u64 {
+    match operation {
+        InviteOperation::Create => 0,
+        InviteOperation::Revoke => 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InviteCommand {
+    pub id: String,
+    pub operation: InviteOperation, // Encoded as 8 bytes
+    pub code: String,
+    pub lifetime: u64,
+    pub max_uses: u64
+}
+
+impl InviteCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<InviteCommand, String> {
+        InviteCommand::deserialize(data)
+    }
+
+    /**
+     * Create an InviteCommand requesting a new code.
+     */
+    pub fn create(lifetime: u64, max_uses: u64) -> InviteCommand {
+        InviteCommand {
+            id: String::from(INVITE_MSG_ID),
+            operation: InviteOperation::Create,
+            code: String::new(),
+            lifetime,
+            max_uses
+        }
+    }
+
+    /**
+     * Create an InviteCommand revoking an existing code.
+     */
+    pub fn revoke(code: &str) -> InviteCommand {
+        InviteCommand {
+            id: String::from(INVITE_MSG_ID),
+            operation: InviteOperation::Revoke,
+            code: String::from(code),
+            lifetime: 0,
+            max_uses: 0
+        }
+    }
+
+    /**
+     * Deserialize a buffer into an InviteCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<InviteCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = InviteCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            operation: u64_to_invite_operation(read_u64_field(&mut reader)?)?,
+            code: read_padded_string(&mut reader, 16)?,
+            lifetime: read_u64_field(&mut reader)?,
+            max_uses: read_u64_field(&mut reader)?
+        };
+
+        if res.id != INVITE_MSG_ID {
+            return Err(format!("InviteCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the InviteCommand instance.
+     *
+     * Wire format : [id:8][operation:8][code:16][lifetime:8][max_uses:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(invite_operation_to_u64(self.operation)),
+            pad_string(self.code.as_bytes(), 16),
+            u64_to_buf(self.lifetime),
+            u64_to_buf(self.max_uses)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Reply to an INVITE creation, contains the code and the time at which it
+ * expires as a unix timestamp.
+ */
+#[derive(Debug, PartialEq)]
+pub struct InviteReply {
+    pub id: String,
+    pub code: String,
+    pub expires_at: u64
+}
+
+impl InviteReply {
+    pub fn from_info(code: &str, expires_at: u64) -> InviteReply {
+        InviteReply {
+            id: String::from(INVITE_MSG_ID),
+            code: String::from(code),
+            expires_at
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<InviteReply, String> {
+        let mut reader = BufReader::new(data);
+
+        Ok(InviteReply {
+            id: read_padded_string(&mut reader, 8)?,
+            code: read_padded_string(&mut reader, 16)?,
+            expires_at: read_u64_field(&mut reader)?
+        })
+    }
+
+    /**
+     * Wire format : [id:8][code:16][expires_at:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.code.as_bytes(), 16),
+            u64_to_buf(self.expires_at)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_roundtrip() {
+        let cmd = InviteCommand::create(3600, 5);
+        let output = InviteCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+
+        let cmd = InviteCommand::revoke("K7PQ2M");
+        let output = InviteCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_invite_reply_roundtrip() {
+        let reply = InviteReply::from_info("K7PQ2M", 1_600_000_000);
+
+        let output = InviteReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+    }
+}
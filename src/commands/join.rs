@@ -0,0 +1,145 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static JOIN_MSG_ID: &str = "JOIN";
+
+// API Call : JOIN
+//
+// Adds the client to the list of players in a game session. The password
+// must match the one given by the host when the session was created.
+//
+// Spectators join the session without taking a player slot, they can read
+// the session but not write to it.
+//
+// When an invite code is given, the name and password are ignored and the
+// client joins the session the code was created for.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum JoinMode {
+    Player,
+    Spectator
+}
+
+fn u64_to_join_mode(value: u64) -> Result<JoinMode, String> {
+    match value {
+        0 => Ok(JoinMode::Player),
+        1 => Ok(JoinMode::Spectator),
+        _ => Err(format!("Unknown join mode {}", value))
+    }
+}
+
+fn join_mode_to_u64(mode: JoinMode) -> u64 {
+    match mode {
+        JoinMode::Player => 0,
+        JoinMode::Spectator => 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct JoinCommand {
+    pub id: String,
+    pub name: String,
+    pub password: String,
+    pub mode: JoinMode, // Encoded as 8 bytes
+    pub invite: String
+}
+
+impl JoinCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<JoinCommand, String> {
+        JoinCommand::deserialize(data)
+    }
+
+    /**
+     * Create a JoinCommand with the information provided.
+     */
+    pub fn from_info(name: &str, password: &str, mode: JoinMode) -> JoinCommand {
+        JoinCommand {
+            id: String::from(JOIN_MSG_ID),
+            name: String::from(name),
+            password: String::from(password),
+            mode,
+            invite: String::new()
+        }
+    }
+
+    /**
+     * Create a JoinCommand using an invite code.
+     */
+    pub fn from_invite(code: &str, mode: JoinMode) -> JoinCommand {
+        JoinCommand {
+            id: String::from(JOIN_MSG_ID),
+            name: String::new(),
+            password: String::new(),
+            mode,
+            invite: String::from(code)
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a JoinCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<JoinCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = JoinCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            name: read_padded_string(&mut reader, 32)?,
+            password: read_padded_string(&mut reader, 32)?,
+            mode: u64_to_join_mode(read_u64_field(&mut reader)?)?,
+            invite: read_padded_string(&mut reader, 16)?
+        };
+
+        if res.id != JOIN_MSG_ID {
+            return Err(format!("JoinCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Serialize the JoinCommand instance.
+     *
+     * Wire format : [id:8][name:32][password:32][mode:8][invite:16]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            pad_string(self.password.as_bytes(), 32),
+            u64_to_buf(join_mode_to_u64(self.mode)),
+            pad_string(self.invite.as_bytes(), 16)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_roundtrip() {
+        let cmd = JoinCommand::from_info("Caribbean", "Rum", JoinMode::Player);
+
+        let output = JoinCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+
+        let cmd = JoinCommand::from_info("Caribbean", "Rum", JoinMode::Spectator);
+
+        let output = JoinCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(output.mode, JoinMode::Spectator);
+
+        let cmd = JoinCommand::from_invite("K7PQ2M", JoinMode::Player);
+
+        let output = JoinCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(output.invite, "K7PQ2M");
+    }
+
+    #[test]
+    fn test_join_truncated() {
+        let bytes = JoinCommand::from_info("Caribbean", "Rum", JoinMode::Player).serialize();
+
+        JoinCommand::deserialize(&bytes[0..40]).expect_err("Password is missing");
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static KICK_MSG_ID: &str = "KICK";
+pub static BAN_MSG_ID: &str = "BAN";
+
+// API Call : KICK / BAN
+//
+// Sent by the host of a game session to remove a player or a spectator from
+// the session. Both messages share the same structure, BAN also prevents the
+// player from joining the session again, using either the same name or the
+// same connection address.
+//
+// The removed client receives the same message with its own name and the
+// reason given by the host. When `close` is set the server also closes the
+// connection of the removed client.
+
+#[derive(Debug, PartialEq)]
+pub struct KickCommand {
+    pub id: String,
+    pub player: String,
+    pub close: bool, // Encoded as 8 bytes
+    pub reason: String
+}
+
+impl KickCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<KickCommand, String> {
+        KickCommand::deserialize(data)
+    }
+
+    pub fn kick(player: &str, close: bool, reason: &str) -> KickCommand {
+        KickCommand {
+            id: String::from(KICK_MSG_ID),
+            player: String::from(player),
+            close,
+            reason: String::from(reason)
+        }
+    }
+
+    pub fn ban(player: &str, close: bool, reason: &str) -> KickCommand {
+        KickCommand {
+            id: String::from(BAN_MSG_ID),
+            ..KickCommand::kick(player, close, reason)
+        }
+    }
+
+    pub fn is_ban(&self) -> bool {
+        self.id == BAN_MSG_ID
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<KickCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let player = read_padded_string(&mut reader, 32)?;
+        let close = read_u64_field(&mut reader)? != 0;
+
+        let mut reason_bytes = vec![];
+        reader.read_to_end(&mut reason_bytes).map_err(|_| "Unable to read reason from buffer.")?;
+
+        if id != KICK_MSG_ID && id != BAN_MSG_ID {
+            return Err(format!("KickCommand has invalid ID [{}]", id));
+        }
+
+        Ok(KickCommand {
+            id,
+            player,
+            close,
+            reason: vec_to_trimmed_string(&reason_bytes).map_err(|_| "Kick reason is not UTF8.")?
+        })
+    }
+
+    /**
+     * Wire format : [id:8][player:32][close:8][reason:rest]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.player.as_bytes(), 32),
+            u64_to_buf(self.close as u64),
+            self.reason.as_bytes().to_vec()
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kick_roundtrip() {
+        let cmd = KickCommand::kick("Griefer", false, "Ramming the host");
+        let output = KickCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+        assert!(!output.is_ban());
+
+        let cmd = KickCommand::ban("Griefer", true, "Ramming the host again");
+        let output = KickCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+        assert!(output.is_ban());
+    }
+}
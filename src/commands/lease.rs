@@ -0,0 +1,126 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static LEASE_MSG_ID: &str = "LEASE";
+
+// API Call : LEASE
+//
+// Reserve a block of object ids, so a client can add objects without waiting
+// for the server to choose their ids. The server answers with a LEASE reply
+// holding the first id of the block, the block goes from it to
+// `start + count - 1`. Ids handed out by the server are never used by another
+// object.
+//
+// Leased ids can only be used by the player holding the lease, other players
+// adding an object with one of them get a Conflict status. Leases end when the
+// player disconnects or leaves the game session, the ids not used by then are
+// not handed out again. A player holds at most MAX_LEASES leases at once.
+//
+// Clients that do not need ids up front add objects with an object id of 0,
+// see PUTOBJ.
+
+// Largest number of ids leased by a single LEASE.
+pub static MAX_LEASE_SIZE: u64 = 4096;
+
+#[derive(Debug, PartialEq)]
+pub struct LeaseCommand {
+    pub id: String,
+    pub count: u64
+}
+
+impl LeaseCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<LeaseCommand, String> {
+        LeaseCommand::deserialize(data)
+    }
+
+    pub fn from_info(count: u64) -> LeaseCommand {
+        LeaseCommand {
+            id: String::from(LEASE_MSG_ID),
+            count
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<LeaseCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = LeaseCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            count: read_u64_field(&mut reader)?
+        };
+
+        if res.id != LEASE_MSG_ID {
+            return Err(format!("LeaseCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][count:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.count)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Sent to the client with the block of ids it leased.
+ */
+#[derive(Debug, PartialEq)]
+pub struct LeaseReply {
+    pub id: String,
+    pub start: u64,
+    pub count: u64
+}
+
+impl LeaseReply {
+    pub fn from_info(start: u64, count: u64) -> LeaseReply {
+        LeaseReply {
+            id: String::from(LEASE_MSG_ID),
+            start,
+            count
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<LeaseReply, String> {
+        let mut reader = BufReader::new(data);
+
+        Ok(LeaseReply {
+            id: read_padded_string(&mut reader, 8)?,
+            start: read_u64_field(&mut reader)?,
+            count: read_u64_field(&mut reader)?
+        })
+    }
+
+    /**
+     * Wire format : [id:8][start:8][count:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.start),
+            u64_to_buf(self.count)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_roundtrip() {
+        let cmd = LeaseCommand::from_info(64);
+        assert_eq!(LeaseCommand::deserialize(&cmd.serialize()).unwrap(), cmd);
+
+        let reply = LeaseReply::from_info(1000, 64);
+        assert_eq!(LeaseReply::deserialize(&reply.serialize()).unwrap(), reply);
+    }
+}
@@ -1,4 +1,4 @@
-/**
+/*!
  * Commands Module
  *
  * This module contains all server commands that can be sent/received by the
@@ -8,8 +8,52 @@
 pub mod hello;
 pub mod bye;
 pub mod putobj;
+pub mod status;
+pub mod create;
+pub mod join;
+pub mod grant;
+pub mod gameinfo;
+pub mod invite;
+pub mod phase;
+pub mod kick;
+pub mod getobj;
+pub mod changes;
+pub mod subscribe;
+pub mod interest;
+pub mod values;
+pub mod class;
+pub mod authority;
+pub mod transaction;
+pub mod snapshot;
+pub mod rewind;
+pub mod query;
+pub mod lease;
+pub mod template;
+pub mod team;
 
 use crate::utils;
 pub use hello::*;
 pub use bye::*;
-pub use putobj::*;
\ No newline at end of file
+pub use putobj::*;
+pub use status::*;
+pub use create::*;
+pub use join::*;
+pub use grant::*;
+pub use gameinfo::*;
+pub use invite::*;
+pub use phase::*;
+pub use kick::*;
+pub use getobj::*;
+pub use changes::*;
+pub use subscribe::*;
+pub use interest::*;
+pub use values::*;
+pub use class::*;
+pub use authority::*;
+pub use transaction::*;
+pub use snapshot::*;
+pub use rewind::*;
+pub use query::*;
+pub use lease::*;
+pub use template::*;
+pub use team::*;
\ No newline at end of file
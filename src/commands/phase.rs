@@ -0,0 +1,219 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static READY_MSG_ID: &str = "READY";
+pub static PHASE_MSG_ID: &str = "PHASE";
+
+// API Call : READY
+//
+// Sent by a player in the lobby of a game session to toggle its ready flag.
+//
+// API Call : PHASE
+//
+// Sent by the host of a game session to move the session to another phase.
+// Moving to Countdown starts the game after `countdown` seconds, moving back
+// to Lobby cancels a countdown and moving to Finished ends a running game.
+//
+// The server broadcasts every phase change to the session with a PHASE reply,
+// including the move to Running once the countdown ends. The start timestamp
+// of that reply is the time, in unix milliseconds, at which the game starts
+// running so every client starts at the same time. Countdowns above
+// MAX_COUNTDOWN seconds are rejected with an Invalid status.
+
+// Longest countdown before a game starts, in seconds : one hour.
+pub static MAX_COUNTDOWN: u64 = 3600;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GamePhase {
+    Lobby,
+    Countdown,
+    Running,
+    Finished
+}
+
+pub fn u64_to_game_phase(value: u64) -> Result<GamePhase, String> {
+    match value {
+        0 => Ok(GamePhase::Lobby),
+        1 => Ok(GamePhase::Countdown),
+        2 => Ok(GamePhase::Running),
+        3 => Ok(GamePhase::Finished),
+        _ => Err(format!("Unknown game phase {}", value))
+    }
+}
+
+pub fn game_phase_to_u64(phase: GamePhase) -> u64 {
+    match phase {
+        GamePhase::Lobby => 0,
+        GamePhase::Countdown => 1,
+        GamePhase::Running => 2,
+        GamePhase::Finished => 3
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReadyCommand {
+    pub id: String,
+    pub ready: bool // Encoded as 8 bytes
+}
+
+impl ReadyCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<ReadyCommand, String> {
+        ReadyCommand::deserialize(data)
+    }
+
+    pub fn from_info(ready: bool) -> ReadyCommand {
+        ReadyCommand {
+            id: String::from(READY_MSG_ID),
+            ready
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<ReadyCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = ReadyCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            ready: read_u64_field(&mut reader)? != 0
+        };
+
+        if res.id != READY_MSG_ID {
+            return Err(format!("ReadyCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][ready:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.ready as u64)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PhaseCommand {
+    pub id: String,
+    pub phase: GamePhase, // Encoded as 8 bytes
+    pub countdown: u64
+}
+
+impl PhaseCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<PhaseCommand, String> {
+        PhaseCommand::deserialize(data)
+    }
+
+    pub fn from_info(phase: GamePhase, countdown: u64) -> PhaseCommand {
+        PhaseCommand {
+            id: String::from(PHASE_MSG_ID),
+            phase,
+            countdown
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<PhaseCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = PhaseCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            phase: u64_to_game_phase(read_u64_field(&mut reader)?)?,
+            countdown: read_u64_field(&mut reader)?
+        };
+
+        if res.id != PHASE_MSG_ID {
+            return Err(format!("PhaseCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][phase:8][countdown:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(game_phase_to_u64(self.phase)),
+            u64_to_buf(self.countdown)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Broadcast to a game session when its phase changes. The start timestamp is
+ * 0 while the session is in the lobby.
+ */
+#[derive(Debug, PartialEq)]
+pub struct PhaseReply {
+    pub id: String,
+    pub phase: GamePhase, // Encoded as 8 bytes
+    pub start_at: u64
+}
+
+impl PhaseReply {
+    pub fn from_info(phase: GamePhase, start_at: u64) -> PhaseReply {
+        PhaseReply {
+            id: String::from(PHASE_MSG_ID),
+            phase,
+            start_at
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<PhaseReply, String> {
+        let mut reader = BufReader::new(data);
+
+        Ok(PhaseReply {
+            id: read_padded_string(&mut reader, 8)?,
+            phase: u64_to_game_phase(read_u64_field(&mut reader)?)?,
+            start_at: read_u64_field(&mut reader)?
+        })
+    }
+
+    /**
+     * Wire format : [id:8][phase:8][start_at:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(game_phase_to_u64(self.phase)),
+            u64_to_buf(self.start_at)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_roundtrip() {
+        let cmd = ReadyCommand::from_info(true);
+
+        let output = ReadyCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_phase_roundtrip() {
+        let cmd = PhaseCommand::from_info(GamePhase::Countdown, 5);
+        let output = PhaseCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+
+        let reply = PhaseReply::from_info(GamePhase::Running, 1_600_000_000_000);
+        let output = PhaseReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+
+        let mut bytes = cmd.serialize();
+        bytes[8] = 9;
+        PhaseCommand::deserialize(&bytes).expect_err("Phase is unknown");
+    }
+}
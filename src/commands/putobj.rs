@@ -1,24 +1,101 @@
 use std::iter::*;
 use std::io::{BufReader, Read};
 use crate::utils::*;
+use super::values::*;
+use super::authority::*;
 
 extern crate byteorder;
 use byteorder::*;
 use std::convert::*;
 
 pub static PUTOBJ_MSG_ID: &str = "PUTOBJ";
+pub static OPRESULT_MSG_ID: &str = "OPRESULT";
+
+// Longest ttl of an object, in seconds.
+pub static MAX_TTL: u64 = 7 * 24 * 3600;
 
 // API Call : PUTOBJ
 //
 // Uploads a game object and a set of properties to the server. The caller
 // can set several options when calling this method such as deleting an object
 // and updating its properties.
+//
+// Objects are identified by their id. The name, the class, the owner leave
+// policy and the ttl are only read when adding an object. The name lets
+// clients query objects by name, the class makes the server check the
+// properties against the class schema. The player adding an object owns it,
+// see AUTH.
+//
+// Adding an object with an id of 0 lets the server choose a free id, returned
+// in the PUTOBJ reply. Ids from SERVER_ID_START on are handed out by the
+// server only, clients can use them when they leased them, see LEASE. Ids
+// below it can be chosen freely by clients.
+//
+// The lifetime of an object is set when it is added :
+//
+// - Persistent : RevertToHost policy and a ttl of 0, the object stays until
+//   it is deleted.
+// - Owner session : Delete policy, the object is deleted when its owner
+//   leaves the game session or disconnects.
+// - Expiring : a ttl in seconds, at most MAX_TTL, the object is deleted if it
+//   was not written during that time. Every Update refreshes it, even without
+//   properties.
+//
+// Objects can have a parent, given by parent_id when adding them or changed
+// later with the Reparent operation. A parent_id of 0 means no parent.
+// Deleting an object also deletes all its descendants.
+//
+// Every change increments the version of the object. Update and Delete can
+// give the version the client expects the object to be at, the change is then
+// rejected with a Conflict status if another client changed the object first.
+// An expected version of 0 skips the check. On success the server answers with
+// a PUTOBJ reply containing the new version of the object.
+//
+// Atomic operations change a single property of an existing object on the
+// server, so concurrent clients do not lose each other's updates. The first
+// property of the command names the property to change and holds the first
+// operand, the names of the other properties are ignored :
+//
+// - Increment, Decrement : the first operand is a delta of the type of the
+//   property, which must be declared Int or Float by the class of the object.
+//   A missing property starts at 0. The optional second and third operands
+//   are the lowest and highest values allowed.
+// - CompareAndSwap : the property is set to the second operand if its value
+//   is the first operand.
+// - Append : the operand is added at the end of a List property, a missing
+//   property becomes a list with only the operand. The operand is encoded
+//   like the items of a List, with its type, see PropertyValue.
+// - Remove : the first item of a List property equal to the operand is
+//   removed, the operand is encoded like for Append.
+// - SetIfAbsent : the property is set to the operand if it does not exist.
+//
+// The server answers with an OPRESULT reply telling if the operation was
+// applied, with the value of the property afterwards. When it was not, for
+// example when a bound would be crossed or the compared value changed, the
+// object is left untouched and the reply holds the current value. Inside a
+// TXN an atomic operation that is not applied fails the transaction.
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum PutOperation {
     Add,
     Update,
-    Delete
+    Delete,
+    Reparent,
+    Increment,
+    Decrement,
+    CompareAndSwap,
+    Append,
+    Remove,
+    SetIfAbsent
+}
+
+impl PutOperation {
+    /**
+     * Checks if the operation is an atomic operation on a single property.
+     */
+    pub fn is_atomic(&self) -> bool {
+        !matches!(self, PutOperation::Add | PutOperation::Update | PutOperation::Delete | PutOperation::Reparent)
+    }
 }
 
 fn u64_to_put_operation(value: u64) -> Result<PutOperation, String> {
@@ -26,56 +103,278 @@ fn u64_to_put_operation(value: u64) -> Result<PutOperation, String> {
         0 => Ok(PutOperation::Add),
         1 => Ok(PutOperation::Delete),
         2 => Ok(PutOperation::Update),
+        3 => Ok(PutOperation::Reparent),
+        4 => Ok(PutOperation::Increment),
+        5 => Ok(PutOperation::Decrement),
+        6 => Ok(PutOperation::CompareAndSwap),
+        7 => Ok(PutOperation::Append),
+        8 => Ok(PutOperation::Remove),
+        9 => Ok(PutOperation::SetIfAbsent),
         _ => Err(String::from("Unknown value"))
     }
 }
 
-#[derive(Debug)]
+fn put_operation_to_u64(operation: PutOperation) -> u64 {
+    match operation {
+        PutOperation::Add => 0,
+        PutOperation::Delete => 1,
+        PutOperation::Update => 2,
+        PutOperation::Reparent => 3,
+        PutOperation::Increment => 4,
+        PutOperation::Decrement => 5,
+        PutOperation::CompareAndSwap => 6,
+        PutOperation::Append => 7,
+        PutOperation::Remove => 8,
+        PutOperation::SetIfAbsent => 9
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct PutObjCommand {
     pub id: String,
     pub operation: PutOperation, // Encoded as 8 bytes
+    pub object_id: u64,
+    pub object_name: String,
+    pub class_name: String,
+    pub on_owner_leave: OwnerLeavePolicy, // Encoded as 8 bytes
+    pub ttl: u64,
+    pub parent_id: u64,
+    pub expected_version: u64,
     pub properties: Vec<ObjProperties>
 }
 
 impl PutObjCommand {
     pub fn from_client_message(data: &[u8]) -> Result<PutObjCommand, String> {
-        Ok(PutObjCommand::from(data))
+        PutObjCommand::deserialize(data)
     }
-}
 
-impl From::<&[u8]> for PutObjCommand {
-    fn from(buffer: &[u8]) -> Self {
+    /**
+     * Create a PutObjCommand with the information provided.
+     */
+    pub fn from_info(operation: PutOperation, object_id: u64, object_name: &str, properties: Vec<ObjProperties>) -> PutObjCommand {
+        PutObjCommand {
+            id: String::from(PUTOBJ_MSG_ID),
+            operation,
+            object_id,
+            object_name: String::from(object_name),
+            class_name: String::new(),
+            on_owner_leave: OwnerLeavePolicy::RevertToHost,
+            ttl: 0,
+            parent_id: 0,
+            expected_version: 0,
+            properties
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a PutObjCommand instance.
+     */
+    pub fn deserialize(buffer: &[u8]) -> Result<PutObjCommand, String> {
         let mut reader = BufReader::new(buffer);
 
-        let mut id_bytes = [0; 8];
-        reader.read_exact(&mut id_bytes).unwrap();
+        PutObjCommand::try_from(&mut reader)
+    }
+
+    /**
+     * Serialize the PutObjCommand instance.
+     *
+     * Wire format : [id:8][operation:8][object_id:8][object_name:32]
+     *               [class_name:32][on_owner_leave:8][ttl:8][parent_id:8]
+     *               [expected_version:8][properties count:8][properties...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(put_operation_to_u64(self.operation)),
+            u64_to_buf(self.object_id),
+            pad_string(self.object_name.as_bytes(), 32),
+            pad_string(self.class_name.as_bytes(), 32),
+            u64_to_buf(owner_leave_policy_to_u64(self.on_owner_leave)),
+            u64_to_buf(self.ttl),
+            u64_to_buf(self.parent_id),
+            u64_to_buf(self.expected_version),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for prop in &self.properties {
+            result.push(prop.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Sent to the client when its PUTOBJ was applied.
+ */
+#[derive(Debug, PartialEq)]
+pub struct PutObjReply {
+    pub id: String,
+    pub object_id: u64,
+    pub version: u64
+}
+
+impl PutObjReply {
+    pub fn from_info(object_id: u64, version: u64) -> PutObjReply {
+        PutObjReply::for_request(PUTOBJ_MSG_ID, object_id, version)
+    }
+
+    /**
+     * Create a reply with the PUTOBJ layout answering another request.
+     */
+    pub fn for_request(request: &str, object_id: u64, version: u64) -> PutObjReply {
+        PutObjReply {
+            id: String::from(request),
+            object_id,
+            version
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<PutObjReply, String> {
+        let mut reader = BufReader::new(data);
 
-        let mut oper_bytes = [0; 8];
-        reader.read_exact(&mut oper_bytes).unwrap();
+        Ok(PutObjReply {
+            id: read_padded_string(&mut reader, 8)?,
+            object_id: read_u64_field(&mut reader)?,
+            version: read_u64_field(&mut reader)?
+        })
+    }
 
-        let mut prop_len_bytes = [0; 8];
-        reader.read_exact(&mut prop_len_bytes).unwrap();
+    /**
+     * Wire format : [id:8][object_id:8][version:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.object_id),
+            u64_to_buf(self.version)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
 
-        let prop_len: usize = buf_to_u64(prop_len_bytes).try_into().unwrap();
+/**
+ * Sent to the client when its atomic operation was processed.
+ */
+#[derive(Debug, PartialEq)]
+pub struct OpResultReply {
+    pub id: String,
+    pub object_id: u64,
+    pub version: u64,
+    pub applied: bool, // Encoded as 8 bytes
+    // Property after the operation, None when it does not exist.
+    pub value: Option<ObjProperties>
+}
+
+impl OpResultReply {
+    pub fn from_info(object_id: u64, version: u64, applied: bool, value: Option<ObjProperties>) -> OpResultReply {
+        OpResultReply {
+            id: String::from(OPRESULT_MSG_ID),
+            object_id,
+            version,
+            applied,
+            value
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<OpResultReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let object_id = read_u64_field(&mut reader)?;
+        let version = read_u64_field(&mut reader)?;
+        let applied = read_u64_field(&mut reader)? != 0;
+        let value = match read_u64_field(&mut reader)? {
+            0 => None,
+            _ => Some(ObjProperties::try_from(&mut reader)?)
+        };
+
+        if id != OPRESULT_MSG_ID {
+            return Err(format!("OpResultReply has invalid ID [{}]", id));
+        }
+
+        Ok(OpResultReply { id, object_id, version, applied, value })
+    }
+
+    /**
+     * Wire format : [id:8][object_id:8][version:8][applied:8]
+     *               [value count:8][value property, when the count is 1]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.object_id),
+            u64_to_buf(self.version),
+            u64_to_buf(self.applied as u64),
+            u64_to_buf(self.value.is_some() as u64)
+        ];
+
+        if let Some(value) = &self.value {
+            result.push(value.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Read a PUTOBJ from a reader, so several of them can be read from the same
+ * buffer.
+ */
+impl TryFrom::<&mut BufReader<&[u8]>> for PutObjCommand {
+    type Error = String;
+
+    fn try_from(reader: &mut BufReader<&[u8]>) -> Result<Self, Self::Error> {
+        let id = read_padded_string(reader, 8)?;
+        let operation = u64_to_put_operation(read_u64_field(reader)?)?;
+        let object_id = read_u64_field(reader)?;
+        let object_name = read_padded_string(reader, 32)?;
+        let class_name = read_padded_string(reader, 32)?;
+        let on_owner_leave = u64_to_owner_leave_policy(read_u64_field(reader)?)?;
+        let ttl = read_u64_field(reader)?;
+        let parent_id = read_u64_field(reader)?;
+        let expected_version = read_u64_field(reader)?;
+
+        let prop_len = read_u64_field(reader)?;
 
         let mut properties_list = vec![];
         for _i in 0..prop_len {
-            let read_prop = ObjProperties::try_from(&mut reader).unwrap();
+            let read_prop = ObjProperties::try_from(&mut *reader)?;
 
             properties_list.push(read_prop);
         }
 
+        if id != PUTOBJ_MSG_ID {
+            return Err(format!("PutObjCommand has invalid ID [{}]", id));
+        }
+
         let res = PutObjCommand {
-            id: String::from("PUTOBJ"),
-            operation: u64_to_put_operation(buf_to_u64(oper_bytes)).unwrap(),
+            id,
+            operation,
+            object_id,
+            object_name,
+            class_name,
+            on_owner_leave,
+            ttl,
+            parent_id,
+            expected_version,
             properties: properties_list
         };
 
-        return res;
+        Ok(res)
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl TryFrom::<&[u8]> for PutObjCommand {
+    type Error = String;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        PutObjCommand::deserialize(buffer)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct ObjProperties {
     pub name: String,
     pub length: u32,
@@ -84,25 +383,54 @@ pub struct ObjProperties {
 
 impl ObjProperties {
 
+    /**
+     * Create a property from its data, kept as is by the server.
+     */
+    pub fn raw(name: &str, data: &[u8]) -> ObjProperties {
+        ObjProperties {
+            name: String::from(name),
+            length: usize_to_u32(data.len()),
+            data: data.to_vec()
+        }
+    }
+
+    /**
+     * Create a property holding an encoded value.
+     */
+    pub fn typed(name: &str, value: &PropertyValue) -> ObjProperties {
+        ObjProperties::raw(name, &value.encode())
+    }
+
+    /**
+     * Decode the data as a value of a type. The type is not sent with the
+     * property, it comes from the class of the object, see CLASS.
+     */
+    pub fn value_as(&self, value_type: ValueType) -> Result<PropertyValue, String> {
+        PropertyValue::decode(value_type, &self.data)
+                      .map_err(|e| format!("Invalid property {} : {}", self.name, e))
+    }
+
     /**
      * Serialize the structure to a binary vector.
      */
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut result: Vec<Vec<u8>> = vec![];
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.name.as_bytes(), 8),
+            u32_to_buf(usize_to_u32(self.data.len())),
+            self.data.to_vec()
+        ];
 
-        result.push(pad_string(self.name.as_bytes(), 8));
-        result.push(u32_to_buf(usize_to_u32(self.data.len())));
-        result.push(self.data.to_vec());
-
-        return result.into_iter().flatten().collect();
+        result.into_iter().flatten().collect()
     }
 }
 
-impl From::<&[u8]> for ObjProperties {
-    fn from(buffer: &[u8]) -> Self {
+impl TryFrom::<&[u8]> for ObjProperties {
+    type Error = String;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
         let mut reader = BufReader::new(buffer);
 
-        ObjProperties::try_from(&mut reader).unwrap()
+        ObjProperties::try_from(&mut reader)
     }
 }
 
@@ -121,7 +449,7 @@ impl TryFrom::<&mut BufReader<&[u8]>> for ObjProperties {
         reader.read_exact(&mut data_buf).map_err(|_| "Unable to read data from buffer.")?;
 
         let result = ObjProperties {
-            name: vec_to_trimmed_string(&name_buf).expect("Object property is not UTF8."),
+            name: vec_to_trimmed_string(&name_buf).map_err(|_| "Object property is not UTF8.")?,
             length: data_buf_len,
             data: data_buf
         };
@@ -134,10 +462,24 @@ impl TryFrom::<&mut BufReader<&[u8]>> for ObjProperties {
  * Struct binary format :
  *
  * [id bytes]
+ * [operation:8 bytes]
+ * [object_id:8 bytes]
+ * [object_name:32 bytes]
+ * [class_name:32 bytes]
+ * [on_owner_leave:8 bytes]
+ * [ttl:8 bytes]
+ * [parent_id:8 bytes]
+ * [expected_version:8 bytes]
+ * [properties count:8 bytes]
+ *
+ * Then for each property :
  *
  * [name:8 bytes]
  * [length:4 bytes]
  * [data:length bytes]
+ *
+ * The data of the properties declared by the class of the object is encoded
+ * as told by their type, see PropertyValue. Other properties are raw data.
  */
 
 #[cfg(test)]
@@ -153,14 +495,19 @@ mod tests {
         };
         let bytes = input.to_bytes();
 
-        let output = ObjProperties::from(&bytes[..]);
+        let output = ObjProperties::try_from(&bytes[..]).unwrap();
 
         assert_eq!(input, output);
 
-        // Test the automatic blanket implementation given by the From trait.
+        // Test the automatic blanket implementation given by the TryFrom trait.
         let output: &[u8] = &bytes;
-        let tester: ObjProperties = output.into();
+        let tester: ObjProperties = output.try_into().unwrap();
         assert_eq!(input, tester);
+
+        // Names must be UTF8.
+        let mut bytes = bytes;
+        bytes[0] = 0xFF;
+        ObjProperties::try_from(&bytes[..]).expect_err("Name is not UTF8");
     }
 
     #[test]
@@ -236,4 +583,52 @@ mod tests {
         assert_eq!(prop2, obj2);
         assert_eq!(prop3, obj3);
     }
+
+    #[test]
+    fn test_putobj_roundtrip() {
+        let props = vec![
+            ObjProperties::raw("Position", &[1; 4]),
+            ObjProperties::typed("Size", &PropertyValue::Vec2(2.0, 2.0))
+        ];
+
+        let mut cmd = PutObjCommand::from_info(PutOperation::Update, 7, "Ship7", props);
+        cmd.expected_version = 3;
+        cmd.class_name = String::from("Ship");
+        cmd.on_owner_leave = OwnerLeavePolicy::Delete;
+        cmd.ttl = 30;
+        cmd.parent_id = 9;
+        let bytes = cmd.serialize();
+
+        // Operation codes are part of the ABI, Update is encoded as 2.
+        assert_eq!(&bytes[8..16], u64_to_buf(2).as_slice());
+
+        let output = PutObjCommand::deserialize(&bytes).unwrap();
+        assert_eq!(cmd, output);
+
+        // Missing properties are reported instead of panicking.
+        PutObjCommand::deserialize(&bytes[0..bytes.len() - 1]).expect_err("Buffer is truncated");
+    }
+
+    #[test]
+    fn test_putobj_reply_roundtrip() {
+        let reply = PutObjReply::from_info(7, 4);
+
+        let output = PutObjReply::deserialize(&reply.serialize()).unwrap();
+        assert_eq!(reply, output);
+    }
+
+    #[test]
+    fn test_opresult_roundtrip() {
+        let cmd = PutObjCommand::from_info(PutOperation::SetIfAbsent, 7, "", vec![ObjProperties::typed("Gold", &PropertyValue::Int(5))]);
+        let bytes = cmd.serialize();
+
+        assert_eq!(&bytes[8..16], u64_to_buf(9).as_slice());
+        assert_eq!(PutObjCommand::deserialize(&bytes).unwrap(), cmd);
+
+        let reply = OpResultReply::from_info(7, 4, true, Some(ObjProperties::typed("Gold", &PropertyValue::Int(12))));
+        assert_eq!(OpResultReply::deserialize(&reply.serialize()).unwrap(), reply);
+
+        let reply = OpResultReply::from_info(7, 4, false, None);
+        assert_eq!(OpResultReply::deserialize(&reply.serialize()).unwrap(), reply);
+    }
 }
\ No newline at end of file
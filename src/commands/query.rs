@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use super::putobj::ObjProperties;
+use super::values::PropertyValue;
+
+pub static QUERY_MSG_ID: &str = "QUERY";
+
+// API Call : QUERY
+//
+// Search the objects of the game session, for example "every Ship owned by
+// Blackbeard with a Health lower than 20". Every criteria is optional :
+//
+// - name_pattern : a glob pattern on the object name, `*` matches any
+//   characters and `?` a single one.
+// - class_name, owner : the exact class and owner of the objects.
+// - filters : comparisons of property values, all of them must hold. The
+//   property of each filter names the property compared and holds the value
+//   to compare it with, encoded with the type the class of the objects
+//   declares for the property. Objects without the property never match
+//   except for NotEqual.
+//
+// Property names can be the logical names of the class of the objects. The
+// results are sorted by `sort_property`, objects without it last, or by id
+// when it is empty. A limit of 0 returns every object found.
+//
+// Queries giving a class name use the indexes declared by the class for their
+// filters, see CLASS. Other queries look at every object of the session.
+//
+// The server answers with the GETOBJ reply layout and the QUERY id, with the
+// properties listed in `property_names` or all of them when it is empty.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Exists
+}
+
+fn u64_to_comparison(value: u64) -> Result<Comparison, String> {
+    match value {
+        0 => Ok(Comparison::Equal),
+        1 => Ok(Comparison::NotEqual),
+        2 => Ok(Comparison::Less),
+        3 => Ok(Comparison::LessOrEqual),
+        4 => Ok(Comparison::Greater),
+        5 => Ok(Comparison::GreaterOrEqual),
+        6 => Ok(Comparison::Exists),
+        _ => Err(format!("Unknown comparison {}", value))
+    }
+}
+
+fn comparison_to_u64(comparison: Comparison) -> u64 {
+    match comparison {
+        Comparison::Equal => 0,
+        Comparison::NotEqual => 1,
+        Comparison::Less => 2,
+        Comparison::LessOrEqual => 3,
+        Comparison::Greater => 4,
+        Comparison::GreaterOrEqual => 5,
+        Comparison::Exists => 6
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueryFilter {
+    pub comparison: Comparison, // Encoded as 8 bytes
+    pub operand: ObjProperties
+}
+
+impl QueryFilter {
+    pub fn new(comparison: Comparison, operand: ObjProperties) -> QueryFilter {
+        QueryFilter { comparison, operand }
+    }
+
+    /**
+     * Checks if the value of a property passes the filter, None when the
+     * object does not have the property.
+     */
+    pub fn matches(&self, value: Option<&PropertyValue>) -> bool {
+        let value = match value {
+            Some(value) => value,
+            None => return self.comparison == Comparison::NotEqual
+        };
+
+        if self.comparison == Comparison::Exists {
+            return true;
+        }
+
+        // The operand is sent without type, it has the type of the property.
+        let operand = match self.operand.value_as(value.value_type()) {
+            Ok(operand) => operand,
+            Err(_) => return false
+        };
+
+        let is_equal = value == &operand || value.compare(&operand) == Some(Ordering::Equal);
+
+        match (self.comparison, value.compare(&operand)) {
+            (Comparison::Equal, _) => is_equal,
+            (Comparison::NotEqual, _) => !is_equal,
+            (Comparison::Less, Some(order)) => order == Ordering::Less,
+            (Comparison::LessOrEqual, Some(order)) => order != Ordering::Greater,
+            (Comparison::Greater, Some(order)) => order == Ordering::Greater,
+            (Comparison::GreaterOrEqual, Some(order)) => order != Ordering::Less,
+            _ => false
+        }
+    }
+
+    /**
+     * Wire format : [comparison:8][property]
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            u64_to_buf(comparison_to_u64(self.comparison)),
+            self.operand.to_bytes()
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+impl TryFrom::<&mut BufReader<&[u8]>> for QueryFilter {
+    type Error = String;
+
+    fn try_from(reader: &mut BufReader<&[u8]>) -> Result<Self, Self::Error> {
+        Ok(QueryFilter {
+            comparison: u64_to_comparison(read_u64_field(reader)?)?,
+            operand: ObjProperties::try_from(reader)?
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct QueryCommand {
+    pub id: String,
+    pub name_pattern: String,
+    pub class_name: String,
+    pub owner: String,
+    pub filters: Vec<QueryFilter>,
+    pub sort_property: String,
+    pub descending: bool, // Encoded as 8 bytes
+    pub limit: u64,
+    pub property_names: Vec<String>
+}
+
+impl QueryCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<QueryCommand, String> {
+        QueryCommand::deserialize(data)
+    }
+
+    /**
+     * Create a query matching every object, criteria are then set on the
+     * fields.
+     */
+    pub fn from_info(filters: Vec<QueryFilter>) -> QueryCommand {
+        QueryCommand {
+            id: String::from(QUERY_MSG_ID),
+            name_pattern: String::new(),
+            class_name: String::new(),
+            owner: String::new(),
+            filters,
+            sort_property: String::new(),
+            descending: false,
+            limit: 0,
+            property_names: vec![]
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<QueryCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let name_pattern = read_padded_string(&mut reader, 32)?;
+        let class_name = read_padded_string(&mut reader, 32)?;
+        let owner = read_padded_string(&mut reader, 32)?;
+        let filters_count = read_u64_field(&mut reader)?;
+
+        let mut filters = vec![];
+        for _i in 0..filters_count {
+            filters.push(QueryFilter::try_from(&mut reader)?);
+        }
+
+        let sort_property = read_padded_string(&mut reader, 8)?;
+        let descending = read_u64_field(&mut reader)? != 0;
+        let limit = read_u64_field(&mut reader)?;
+        let property_names = read_string_list(&mut reader, 8)?;
+
+        if id != QUERY_MSG_ID {
+            return Err(format!("QueryCommand has invalid ID [{}]", id));
+        }
+
+        Ok(QueryCommand { id, name_pattern, class_name, owner, filters, sort_property, descending, limit, property_names })
+    }
+
+    /**
+     * Wire format : [id:8][name_pattern:32][class_name:32][owner:32]
+     *               [filters count:8][filters...][sort_property:8]
+     *               [descending:8][limit:8][properties count:8]
+     *               [property name:8]...
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name_pattern.as_bytes(), 32),
+            pad_string(self.class_name.as_bytes(), 32),
+            pad_string(self.owner.as_bytes(), 32),
+            u64_to_buf(self.filters.len() as u64)
+        ];
+
+        for filter in &self.filters {
+            result.push(filter.to_bytes());
+        }
+
+        result.push(pad_string(self.sort_property.as_bytes(), 8));
+        result.push(u64_to_buf(self.descending as u64));
+        result.push(u64_to_buf(self.limit));
+        result.push(string_list_to_buf(&self.property_names, 8));
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_roundtrip() {
+        let mut cmd = QueryCommand::from_info(vec![
+            QueryFilter::new(Comparison::Less, ObjProperties::typed("Health", &PropertyValue::Int(20))),
+            QueryFilter::new(Comparison::Exists, ObjProperties::raw("Cargo", b""))
+        ]);
+        cmd.name_pattern = String::from("Ship*");
+        cmd.class_name = String::from("Ship");
+        cmd.owner = String::from("Blackbeard");
+        cmd.sort_property = String::from("Health");
+        cmd.descending = true;
+        cmd.limit = 10;
+        cmd.property_names = vec![String::from("Health")];
+
+        let bytes = cmd.serialize();
+
+        assert_eq!(QueryCommand::deserialize(&bytes).unwrap(), cmd);
+        QueryCommand::deserialize(&bytes[0..bytes.len() - 1]).expect_err("Buffer is truncated");
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let less = QueryFilter::new(Comparison::Less, ObjProperties::typed("Health", &PropertyValue::Int(20)));
+
+        assert!(less.matches(Some(&PropertyValue::Int(5))));
+        assert!(!less.matches(Some(&PropertyValue::Int(20))));
+
+        // The operand is read with the type of the property.
+        let less_float = QueryFilter::new(Comparison::Less, ObjProperties::typed("Speed", &PropertyValue::Float(20.0)));
+        assert!(less_float.matches(Some(&PropertyValue::Float(19.5))));
+        assert!(!less.matches(Some(&PropertyValue::Str(String::from("5")))));
+        assert!(!less.matches(None));
+
+        let not_equal = QueryFilter::new(Comparison::NotEqual, ObjProperties::typed("Flag", &PropertyValue::Bool(true)));
+
+        assert!(not_equal.matches(Some(&PropertyValue::Bool(false))));
+        assert!(!not_equal.matches(Some(&PropertyValue::Bool(true))));
+        assert!(not_equal.matches(None));
+    }
+}
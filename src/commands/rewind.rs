@@ -0,0 +1,116 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+
+pub static REWIND_MSG_ID: &str = "REWIND";
+
+// API Call : REWIND
+//
+// Query the state of an object at a past server time, for example to check
+// if a cannon ball hit a ship where the shooter saw it. The timestamp is in
+// milliseconds since the Unix epoch, like the start time of PHASE replies.
+//
+// The server keeps the past states of the objects for the history duration
+// given when the game session was created. With the Nearest mode the server
+// returns the recorded state closest to the timestamp. With the Interpolate
+// mode Float, Vec2 and Vec3 properties are interpolated between the states
+// recorded before and after the timestamp, other properties have the value of
+// the closest recorded state holding them.
+//
+// The server answers with the GETOBJ reply layout and the REWIND id, holding
+// a single entry. A NotFound status is sent when the object does not exist or
+// has no state recorded at that time.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RewindMode {
+    Nearest,
+    Interpolate
+}
+
+fn u64_to_rewind_mode(value: u64) -> Result<RewindMode, String> {
+    match value {
+        0 => Ok(RewindMode::Nearest),
+        1 => Ok(RewindMode::Interpolate),
+        _ => Err(format!("Unknown rewind mode {}", value))
+    }
+}
+
+fn rewind_mode_to_u64(mode: RewindMode) -> u64 {
+    match mode {
+        RewindMode::Nearest => 0,
+        RewindMode::Interpolate => 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RewindCommand {
+    pub id: String,
+    pub object_id: u64,
+    pub timestamp: u64,
+    pub mode: RewindMode, // Encoded as 8 bytes
+    pub property_names: Vec<String>
+}
+
+impl RewindCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<RewindCommand, String> {
+        RewindCommand::deserialize(data)
+    }
+
+    pub fn from_info(object_id: u64, timestamp: u64, mode: RewindMode, property_names: &[&str]) -> RewindCommand {
+        RewindCommand {
+            id: String::from(REWIND_MSG_ID),
+            object_id,
+            timestamp,
+            mode,
+            property_names: property_names.iter().map(|n| String::from(*n)).collect()
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<RewindCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = RewindCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            object_id: read_u64_field(&mut reader)?,
+            timestamp: read_u64_field(&mut reader)?,
+            mode: u64_to_rewind_mode(read_u64_field(&mut reader)?)?,
+            property_names: read_string_list(&mut reader, 8)?
+        };
+
+        if res.id != REWIND_MSG_ID {
+            return Err(format!("RewindCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][object_id:8][timestamp:8][mode:8]
+     *               [properties count:8][property name:8]...
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.object_id),
+            u64_to_buf(self.timestamp),
+            u64_to_buf(rewind_mode_to_u64(self.mode)),
+            string_list_to_buf(&self.property_names, 8)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewind_roundtrip() {
+        let cmd = RewindCommand::from_info(7, 1_500_000_000_000, RewindMode::Interpolate, &["Position"]);
+        let bytes = cmd.serialize();
+
+        assert_eq!(&bytes[24..32], u64_to_buf(1).as_slice());
+        assert_eq!(RewindCommand::deserialize(&bytes).unwrap(), cmd);
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use crate::commands::getobj::ObjectEntry;
+
+pub static SNAPSHOT_MSG_ID: &str = "SNAPSHOT";
+
+// API Call : SNAPSHOT
+//
+// Fetch the whole state of the game session, for example after joining a game
+// already running. The server answers with one or more SNAPSHOT chunks, each
+// holding up to `chunk_size` objects with all their properties. A chunk size
+// of 0 lets the server choose. Clients with an area of interest only receive
+// the objects inside it and the objects without position.
+//
+// Every chunk has the sequence number of the last change included in the
+// snapshot, the index of the chunk and the number of chunks. The client then
+// continues with CHANGES since that sequence number, and ignores the NOTIFY
+// messages with a sequence number lower or equal to it.
+
+// Number of objects per chunk when the client does not choose.
+pub static DEFAULT_SNAPSHOT_CHUNK: u64 = 64;
+pub static MAX_SNAPSHOT_CHUNK: u64 = 1024;
+
+#[derive(Debug, PartialEq)]
+pub struct SnapshotCommand {
+    pub id: String,
+    pub chunk_size: u64
+}
+
+impl SnapshotCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<SnapshotCommand, String> {
+        SnapshotCommand::deserialize(data)
+    }
+
+    pub fn from_info(chunk_size: u64) -> SnapshotCommand {
+        SnapshotCommand {
+            id: String::from(SNAPSHOT_MSG_ID),
+            chunk_size
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<SnapshotCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = SnapshotCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            chunk_size: read_u64_field(&mut reader)?
+        };
+
+        if res.id != SNAPSHOT_MSG_ID {
+            return Err(format!("SnapshotCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Number of objects to put in each chunk, within the server limits.
+     */
+    pub fn effective_chunk_size(&self) -> u64 {
+        match self.chunk_size {
+            0 => DEFAULT_SNAPSHOT_CHUNK,
+            size => size.min(MAX_SNAPSHOT_CHUNK)
+        }
+    }
+
+    /**
+     * Wire format : [id:8][chunk_size:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.chunk_size)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SnapshotChunk {
+    pub id: String,
+    pub sequence: u64,
+    pub index: u64,
+    pub count: u64,
+    pub objects: Vec<ObjectEntry>
+}
+
+impl SnapshotChunk {
+    /**
+     * Split the objects of a snapshot in chunks. A snapshot always has at
+     * least one chunk so the client learns its sequence number.
+     */
+    pub fn split(sequence: u64, objects: Vec<ObjectEntry>, chunk_size: u64) -> Vec<SnapshotChunk> {
+        let mut chunks: Vec<Vec<ObjectEntry>> = objects.chunks(chunk_size as usize).map(|c| c.to_vec()).collect();
+
+        if chunks.is_empty() {
+            chunks.push(vec![]);
+        }
+
+        let count = chunks.len() as u64;
+
+        chunks.into_iter().enumerate().map(|(index, objects)| SnapshotChunk {
+            id: String::from(SNAPSHOT_MSG_ID),
+            sequence,
+            index: index as u64,
+            count,
+            objects
+        }).collect()
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<SnapshotChunk, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let sequence = read_u64_field(&mut reader)?;
+        let index = read_u64_field(&mut reader)?;
+        let count = read_u64_field(&mut reader)?;
+        let objects_count = read_u64_field(&mut reader)?;
+
+        let mut objects = vec![];
+        for _i in 0..objects_count {
+            objects.push(ObjectEntry::try_from(&mut reader)?);
+        }
+
+        Ok(SnapshotChunk { id, sequence, index, count, objects })
+    }
+
+    /**
+     * Wire format : [id:8][sequence:8][index:8][count:8][objects count:8]
+     *               [objects...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.sequence),
+            u64_to_buf(self.index),
+            u64_to_buf(self.count),
+            u64_to_buf(self.objects.len() as u64)
+        ];
+
+        for object in &self.objects {
+            result.push(object.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let cmd = SnapshotCommand::from_info(0);
+
+        assert_eq!(SnapshotCommand::deserialize(&cmd.serialize()).unwrap(), cmd);
+        assert_eq!(cmd.effective_chunk_size(), DEFAULT_SNAPSHOT_CHUNK);
+        assert_eq!(SnapshotCommand::from_info(100000).effective_chunk_size(), MAX_SNAPSHOT_CHUNK);
+    }
+
+    #[test]
+    fn test_snapshot_chunks() {
+        let objects: Vec<ObjectEntry> = (0..5).map(|i| ObjectEntry::not_found(&format!("Ship{}", i))).collect();
+
+        let chunks = SnapshotChunk::split(42, objects, 2);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].objects.len(), 1);
+        assert!(chunks.iter().all(|c| c.sequence == 42 && c.count == 3));
+
+        let output = SnapshotChunk::deserialize(&chunks[1].serialize()).unwrap();
+        assert_eq!(output, chunks[1]);
+
+        // An empty world still gives one chunk.
+        assert_eq!(SnapshotChunk::split(7, vec![], 2).len(), 1);
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static STATUS_MSG_ID: &str = "STATUS";
+
+// API Reply : STATUS
+//
+// Sent by the server to tell a client how one of its requests was handled.
+// The reply carries the id of the request message it answers, a status code
+// and a human readable message.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum StatusCode {
+    Ok,
+    Denied,
+    NotFound,
+    Invalid,
+    Conflict
+}
+
+fn status_code_to_u64(code: StatusCode) -> u64 {
+    match code {
+        StatusCode::Ok => 0,
+        StatusCode::Denied => 1,
+        StatusCode::NotFound => 2,
+        StatusCode::Invalid => 3,
+        StatusCode::Conflict => 4
+    }
+}
+
+fn u64_to_status_code(value: u64) -> Result<StatusCode, String> {
+    match value {
+        0 => Ok(StatusCode::Ok),
+        1 => Ok(StatusCode::Denied),
+        2 => Ok(StatusCode::NotFound),
+        3 => Ok(StatusCode::Invalid),
+        4 => Ok(StatusCode::Conflict),
+        _ => Err(format!("Unknown status code {}", value))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatusCommand {
+    pub id: String,
+    pub request: String,
+    pub code: StatusCode, // Encoded as 8 bytes
+    pub msg: String
+}
+
+impl StatusCommand {
+    /**
+     * Create a StatusCommand answering the request with the given message id.
+     */
+    pub fn from_info(request: &str, code: StatusCode, msg: &str) -> StatusCommand {
+        StatusCommand {
+            id: String::from(STATUS_MSG_ID),
+            request: String::from(request),
+            code,
+            msg: String::from(msg)
+        }
+    }
+
+    /**
+     * Deserialize a buffer into a StatusCommand instance.
+     */
+    pub fn deserialize(data: &[u8]) -> Result<StatusCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let request = read_padded_string(&mut reader, 8)?;
+        let code = u64_to_status_code(read_u64_field(&mut reader)?)?;
+
+        let mut msg_bytes = vec![];
+        reader.read_to_end(&mut msg_bytes).map_err(|_| "Unable to read message content from buffer.")?;
+
+        if id != STATUS_MSG_ID {
+            return Err(format!("StatusCommand has invalid ID [{}]", id));
+        }
+
+        Ok(StatusCommand {
+            id,
+            request,
+            code,
+            msg: vec_to_trimmed_string(&msg_bytes).map_err(|_| "Status message is not UTF8.")?
+        })
+    }
+
+    /**
+     * Serialize the StatusCommand instance.
+     *
+     * Wire format : [id:8][request:8][code:8][msg:rest]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.request.as_bytes(), 8),
+            u64_to_buf(status_code_to_u64(self.code)),
+            self.msg.as_bytes().to_vec()
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_roundtrip() {
+        let status = StatusCommand::from_info("PUTOBJ", StatusCode::Denied, "Only the host can write objects");
+
+        let bytes = status.serialize();
+        assert_eq!(&bytes[0..8], pad_string(STATUS_MSG_ID.as_bytes(), 8).as_slice());
+        assert_eq!(&bytes[8..16], pad_string(b"PUTOBJ", 8).as_slice());
+        assert_eq!(&bytes[16..24], u64_to_buf(1).as_slice());
+
+        let output = StatusCommand::deserialize(&bytes).unwrap();
+        assert_eq!(status, output);
+    }
+
+    #[test]
+    fn test_status_unknown_code() {
+        let mut bytes = StatusCommand::from_info("JOIN", StatusCode::Ok, "").serialize();
+        bytes[16] = 0xFF;
+
+        StatusCommand::deserialize(&bytes).expect_err("Status code is unknown");
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use crate::commands::getobj::ObjectEntry;
+
+pub static SUB_MSG_ID: &str = "SUB";
+pub static NOTIFY_MSG_ID: &str = "NOTIFY";
+
+// API Call : SUB
+//
+// Subscribe to the changes made to the objects of the game session, or remove
+// a subscription. A subscription matches the objects with the given name, or
+// every object whose name starts with it when `prefix` is set. An empty prefix
+// matches every object of the session.
+//
+// API Push : NOTIFY
+//
+// Pushed by the server to the subscribed clients when a PUTOBJ changes a
+// matching object. The entry only contains the properties that changed and
+// deleted objects have the Deleted status. The sequence number is the one of
+// the change, the same as returned by CHANGES.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SubOperation {
+    Subscribe,
+    Unsubscribe
+}
+
+fn u64_to_sub_operation(value: u64) -> Result<SubOperation, String> {
+    match value {
+        0 => Ok(SubOperation::Subscribe),
+        1 => Ok(SubOperation::Unsubscribe),
+        _ => Err(format!("Unknown subscription operation {}", value))
+    }
+}
+
+fn sub_operation_to_u64(operation: SubOperation) -> u64 {
+    match operation {
+        SubOperation::Subscribe => 0,
+        SubOperation::Unsubscribe => 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SubCommand {
+    pub id: String,
+    pub operation: SubOperation, // Encoded as 8 bytes
+    pub pattern: String,
+    pub prefix: bool // Encoded as 8 bytes
+}
+
+impl SubCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<SubCommand, String> {
+        SubCommand::deserialize(data)
+    }
+
+    pub fn from_info(operation: SubOperation, pattern: &str, prefix: bool) -> SubCommand {
+        SubCommand {
+            id: String::from(SUB_MSG_ID),
+            operation,
+            pattern: String::from(pattern),
+            prefix
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<SubCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = SubCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            operation: u64_to_sub_operation(read_u64_field(&mut reader)?)?,
+            pattern: read_padded_string(&mut reader, 32)?,
+            prefix: read_u64_field(&mut reader)? != 0
+        };
+
+        if res.id != SUB_MSG_ID {
+            return Err(format!("SubCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][operation:8][pattern:32][prefix:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(sub_operation_to_u64(self.operation)),
+            pad_string(self.pattern.as_bytes(), 32),
+            u64_to_buf(self.prefix as u64)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NotifyMessage {
+    pub id: String,
+    pub sequence: u64,
+    pub object: ObjectEntry
+}
+
+impl NotifyMessage {
+    pub fn from_info(sequence: u64, object: ObjectEntry) -> NotifyMessage {
+        NotifyMessage {
+            id: String::from(NOTIFY_MSG_ID),
+            sequence,
+            object
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<NotifyMessage, String> {
+        let mut reader = BufReader::new(data);
+
+        Ok(NotifyMessage {
+            id: read_padded_string(&mut reader, 8)?,
+            sequence: read_u64_field(&mut reader)?,
+            object: ObjectEntry::try_from(&mut reader)?
+        })
+    }
+
+    /**
+     * Wire format : [id:8][sequence:8][object entry]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.sequence),
+            self.object.to_bytes()
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sub_roundtrip() {
+        let cmd = SubCommand::from_info(SubOperation::Subscribe, "Ship", true);
+
+        let output = SubCommand::deserialize(&cmd.serialize()).unwrap();
+        assert_eq!(cmd, output);
+    }
+
+    #[test]
+    fn test_notify_roundtrip() {
+        let msg = NotifyMessage::from_info(12, ObjectEntry::not_found("Ship1"));
+
+        let output = NotifyMessage::deserialize(&msg.serialize()).unwrap();
+        assert_eq!(msg, output);
+    }
+}
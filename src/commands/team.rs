@@ -0,0 +1,78 @@
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+pub static TEAM_MSG_ID: &str = "TEAM";
+
+// API Call : TEAM
+//
+// Sent by the host of a game session to put a player on a team, or to take it
+// off its team with an empty team name. Players on the same team receive the
+// Team properties of the objects owned by each other, see CLASS. Spectators
+// cannot be on a team.
+//
+// The server answers with a STATUS message. The team of a player is kept
+// until it leaves the session.
+
+#[derive(Debug, PartialEq)]
+pub struct TeamCommand {
+    pub id: String,
+    pub player: String,
+    pub team: String
+}
+
+impl TeamCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<TeamCommand, String> {
+        TeamCommand::deserialize(data)
+    }
+
+    pub fn from_info(player: &str, team: &str) -> TeamCommand {
+        TeamCommand {
+            id: String::from(TEAM_MSG_ID),
+            player: String::from(player),
+            team: String::from(team)
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TeamCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let res = TeamCommand {
+            id: read_padded_string(&mut reader, 8)?,
+            player: read_padded_string(&mut reader, 32)?,
+            team: read_padded_string(&mut reader, 32)?
+        };
+
+        if res.id != TEAM_MSG_ID {
+            return Err(format!("TeamCommand has invalid ID [{}]", res.id));
+        }
+
+        Ok(res)
+    }
+
+    /**
+     * Wire format : [id:8][player:32][team:32]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.player.as_bytes(), 32),
+            pad_string(self.team.as_bytes(), 32)
+        ];
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_team_roundtrip() {
+        let cmd = TeamCommand::from_info("Calico Jack", "Pirates");
+        let bytes = cmd.serialize();
+
+        assert_eq!(TeamCommand::deserialize(&bytes).unwrap(), cmd);
+        TeamCommand::deserialize(&bytes[0..bytes.len() - 1]).expect_err("Buffer is truncated");
+    }
+}
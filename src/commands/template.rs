@@ -0,0 +1,357 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use super::putobj::*;
+use super::authority::*;
+
+pub static TEMPLATE_MSG_ID: &str = "TEMPLATE";
+pub static TMPLIST_MSG_ID: &str = "TMPLIST";
+pub static SPAWN_MSG_ID: &str = "SPAWN";
+
+// API Call : TEMPLATE
+//
+// Sent by the host of a game session to register an object template, such as
+// "Sloop" or "Galleon", with the class and the default properties of the
+// objects spawned from it. Registering a template again replaces it and
+// increments its version, the first version is 1.
+//
+// The server answers with a STATUS message, and pushes a TEMPLATE message
+// with the new version of the template to every member of the session so all
+// clients agree on the defaults.
+//
+// API Call : TMPLIST
+//
+// List the templates of the game session, sorted by name. The server answers
+// with a TMPLIST reply holding every template with its version and defaults.
+//
+// API Call : SPAWN
+//
+// Add an object made from a template. The object gets the class and the
+// properties of the template, the properties sent with the command replace
+// the defaults with the same name. The other fields have the same meaning as
+// in PUTOBJ, an object id of 0 lets the server choose it.
+//
+// A template version of 0 uses the latest version of the template, any other
+// version must be the latest one or the spawn is rejected with a Conflict
+// status. On success the server answers with the PUTOBJ reply layout and the
+// SPAWN id.
+
+/**
+ * A template with its defaults, as sent in TEMPLATE and TMPLIST messages.
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub struct TemplateEntry {
+    pub name: String,
+    pub version: u64,
+    pub class_name: String,
+    pub properties: Vec<ObjProperties>
+}
+
+impl TemplateEntry {
+    /**
+     * Wire format : [name:32][version:8][class_name:32][properties count:8]
+     *               [properties...]
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.name.as_bytes(), 32),
+            u64_to_buf(self.version),
+            pad_string(self.class_name.as_bytes(), 32),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for prop in &self.properties {
+            result.push(prop.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+impl TryFrom::<&mut BufReader<&[u8]>> for TemplateEntry {
+    type Error = String;
+
+    fn try_from(reader: &mut BufReader<&[u8]>) -> Result<Self, Self::Error> {
+        let name = read_padded_string(reader, 32)?;
+        let version = read_u64_field(reader)?;
+        let class_name = read_padded_string(reader, 32)?;
+        let count = read_u64_field(reader)?;
+
+        let mut properties = vec![];
+        for _i in 0..count {
+            properties.push(ObjProperties::try_from(&mut *reader)?);
+        }
+
+        Ok(TemplateEntry { name, version, class_name, properties })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateCommand {
+    pub id: String,
+    pub name: String,
+    pub class_name: String,
+    pub properties: Vec<ObjProperties>
+}
+
+impl TemplateCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<TemplateCommand, String> {
+        TemplateCommand::deserialize(data)
+    }
+
+    pub fn from_info(name: &str, class_name: &str, properties: Vec<ObjProperties>) -> TemplateCommand {
+        TemplateCommand {
+            id: String::from(TEMPLATE_MSG_ID),
+            name: String::from(name),
+            class_name: String::from(class_name),
+            properties
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TemplateCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let name = read_padded_string(&mut reader, 32)?;
+        let class_name = read_padded_string(&mut reader, 32)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut properties = vec![];
+        for _i in 0..count {
+            properties.push(ObjProperties::try_from(&mut reader)?);
+        }
+
+        if id != TEMPLATE_MSG_ID {
+            return Err(format!("TemplateCommand has invalid ID [{}]", id));
+        }
+
+        Ok(TemplateCommand { id, name, class_name, properties })
+    }
+
+    /**
+     * Wire format : [id:8][name:32][class_name:32][properties count:8]
+     *               [properties...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.name.as_bytes(), 32),
+            pad_string(self.class_name.as_bytes(), 32),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for prop in &self.properties {
+            result.push(prop.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Templates sent to clients, as a TMPLIST reply or a TEMPLATE push holding
+ * the template that changed.
+ */
+#[derive(Debug, PartialEq)]
+pub struct TemplateListReply {
+    pub id: String,
+    pub templates: Vec<TemplateEntry>
+}
+
+impl TemplateListReply {
+    pub fn for_request(request: &str, templates: Vec<TemplateEntry>) -> TemplateListReply {
+        TemplateListReply {
+            id: String::from(request),
+            templates
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TemplateListReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut templates = vec![];
+        for _i in 0..count {
+            templates.push(TemplateEntry::try_from(&mut reader)?);
+        }
+
+        Ok(TemplateListReply { id, templates })
+    }
+
+    /**
+     * Wire format : [id:8][templates count:8][templates...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.templates.len() as u64)
+        ];
+
+        for template in &self.templates {
+            result.push(template.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateListCommand {
+    pub id: String
+}
+
+impl TemplateListCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<TemplateListCommand, String> {
+        TemplateListCommand::deserialize(data)
+    }
+
+    pub fn from_info() -> TemplateListCommand {
+        TemplateListCommand {
+            id: String::from(TMPLIST_MSG_ID)
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TemplateListCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+
+        if id != TMPLIST_MSG_ID {
+            return Err(format!("TemplateListCommand has invalid ID [{}]", id));
+        }
+
+        Ok(TemplateListCommand { id })
+    }
+
+    /**
+     * Wire format : [id:8]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        pad_string(self.id.as_bytes(), 8)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpawnCommand {
+    pub id: String,
+    pub template: String,
+    pub template_version: u64,
+    pub object_id: u64,
+    pub object_name: String,
+    pub on_owner_leave: OwnerLeavePolicy, // Encoded as 8 bytes
+    pub ttl: u64,
+    pub parent_id: u64,
+    pub properties: Vec<ObjProperties>
+}
+
+impl SpawnCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<SpawnCommand, String> {
+        SpawnCommand::deserialize(data)
+    }
+
+    /**
+     * Create a SpawnCommand using the latest version of the template.
+     */
+    pub fn from_info(template: &str, object_id: u64, object_name: &str, properties: Vec<ObjProperties>) -> SpawnCommand {
+        SpawnCommand {
+            id: String::from(SPAWN_MSG_ID),
+            template: String::from(template),
+            template_version: 0,
+            object_id,
+            object_name: String::from(object_name),
+            on_owner_leave: OwnerLeavePolicy::RevertToHost,
+            ttl: 0,
+            parent_id: 0,
+            properties
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<SpawnCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let template = read_padded_string(&mut reader, 32)?;
+        let template_version = read_u64_field(&mut reader)?;
+        let object_id = read_u64_field(&mut reader)?;
+        let object_name = read_padded_string(&mut reader, 32)?;
+        let on_owner_leave = u64_to_owner_leave_policy(read_u64_field(&mut reader)?)?;
+        let ttl = read_u64_field(&mut reader)?;
+        let parent_id = read_u64_field(&mut reader)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut properties = vec![];
+        for _i in 0..count {
+            properties.push(ObjProperties::try_from(&mut reader)?);
+        }
+
+        if id != SPAWN_MSG_ID {
+            return Err(format!("SpawnCommand has invalid ID [{}]", id));
+        }
+
+        Ok(SpawnCommand { id, template, template_version, object_id, object_name, on_owner_leave, ttl, parent_id, properties })
+    }
+
+    /**
+     * Wire format : [id:8][template:32][template_version:8][object_id:8]
+     *               [object_name:32][on_owner_leave:8][ttl:8][parent_id:8]
+     *               [properties count:8][properties...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            pad_string(self.template.as_bytes(), 32),
+            u64_to_buf(self.template_version),
+            u64_to_buf(self.object_id),
+            pad_string(self.object_name.as_bytes(), 32),
+            u64_to_buf(owner_leave_policy_to_u64(self.on_owner_leave)),
+            u64_to_buf(self.ttl),
+            u64_to_buf(self.parent_id),
+            u64_to_buf(self.properties.len() as u64)
+        ];
+
+        for prop in &self.properties {
+            result.push(prop.to_bytes());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_roundtrip() {
+        let cmd = TemplateCommand::from_info("Sloop", "Ship", vec![ObjProperties::raw("Cannons", b"8")]);
+        assert_eq!(TemplateCommand::deserialize(&cmd.serialize()).unwrap(), cmd);
+
+        let entry = TemplateEntry {
+            name: String::from("Sloop"),
+            version: 2,
+            class_name: String::from("Ship"),
+            properties: cmd.properties.clone()
+        };
+        let reply = TemplateListReply::for_request(TMPLIST_MSG_ID, vec![entry.clone(), entry]);
+        assert_eq!(TemplateListReply::deserialize(&reply.serialize()).unwrap(), reply);
+
+        let list = TemplateListCommand::from_info();
+        assert_eq!(TemplateListCommand::deserialize(&list.serialize()).unwrap(), list);
+    }
+
+    #[test]
+    fn test_spawn_roundtrip() {
+        let mut cmd = SpawnCommand::from_info("Sloop", 0, "Revenge", vec![ObjProperties::raw("Cannons", b"12")]);
+        cmd.template_version = 3;
+        cmd.on_owner_leave = OwnerLeavePolicy::Delete;
+        cmd.ttl = 60;
+        cmd.parent_id = 4;
+
+        let bytes = cmd.serialize();
+
+        assert_eq!(SpawnCommand::deserialize(&bytes).unwrap(), cmd);
+        SpawnCommand::deserialize(&bytes[0..bytes.len() - 1]).expect_err("Buffer is truncated");
+    }
+}
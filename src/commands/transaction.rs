@@ -0,0 +1,159 @@
+use std::io::{BufReader, Read};
+use std::convert::*;
+use crate::utils::*;
+use super::putobj::*;
+
+pub static TXN_MSG_ID: &str = "TXN";
+
+// API Call : TXN
+//
+// Applies several PUTOBJ operations as a whole, for example to move cargo
+// from one ship to another. Either every operation is applied or none of
+// them.
+//
+// Operations are applied in order so a transaction can add an object and
+// update it. Each operation has the same checks as a single PUTOBJ, version
+// checks included. When one fails the server answers with a STATUS message
+// telling which operation failed, and the world is left untouched.
+//
+// On success the server answers with a TXN reply containing the new version of
+// every object in the order of the operations. Subscribers receive the changes
+// of the whole transaction under a single sequence number.
+
+#[derive(Debug, PartialEq)]
+pub struct TxnCommand {
+    pub id: String,
+    pub operations: Vec<PutObjCommand>
+}
+
+impl TxnCommand {
+    pub fn from_client_message(data: &[u8]) -> Result<TxnCommand, String> {
+        TxnCommand::deserialize(data)
+    }
+
+    pub fn from_info(operations: Vec<PutObjCommand>) -> TxnCommand {
+        TxnCommand {
+            id: String::from(TXN_MSG_ID),
+            operations
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TxnCommand, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut operations = vec![];
+        for _i in 0..count {
+            operations.push(PutObjCommand::try_from(&mut reader)?);
+        }
+
+        if id != TXN_MSG_ID {
+            return Err(format!("TxnCommand has invalid ID [{}]", id));
+        }
+
+        Ok(TxnCommand { id, operations })
+    }
+
+    /**
+     * Serialize the TxnCommand instance. Each operation is a complete PUTOBJ
+     * message, its own id included.
+     *
+     * Wire format : [id:8][operations count:8][operations...]
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.operations.len() as u64)
+        ];
+
+        for operation in &self.operations {
+            result.push(operation.serialize());
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+/**
+ * Sent to the client when its transaction was applied.
+ */
+#[derive(Debug, PartialEq)]
+pub struct TxnReply {
+    pub id: String,
+    pub sequence: u64,
+    // Object id and version after the transaction, for each operation.
+    pub versions: Vec<(u64, u64)>
+}
+
+impl TxnReply {
+    pub fn from_info(sequence: u64, versions: Vec<(u64, u64)>) -> TxnReply {
+        TxnReply {
+            id: String::from(TXN_MSG_ID),
+            sequence,
+            versions
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<TxnReply, String> {
+        let mut reader = BufReader::new(data);
+
+        let id = read_padded_string(&mut reader, 8)?;
+        let sequence = read_u64_field(&mut reader)?;
+        let count = read_u64_field(&mut reader)?;
+
+        let mut versions = vec![];
+        for _i in 0..count {
+            versions.push((read_u64_field(&mut reader)?, read_u64_field(&mut reader)?));
+        }
+
+        Ok(TxnReply { id, sequence, versions })
+    }
+
+    /**
+     * Wire format : [id:8][sequence:8][count:8] then [object_id:8][version:8]
+     *               for each operation
+     */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<Vec<u8>> = vec![
+            pad_string(self.id.as_bytes(), 8),
+            u64_to_buf(self.sequence),
+            u64_to_buf(self.versions.len() as u64)
+        ];
+
+        for (object_id, version) in &self.versions {
+            result.push(u64_to_buf(*object_id));
+            result.push(u64_to_buf(*version));
+        }
+
+        result.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txn_roundtrip() {
+        let mut update = PutObjCommand::from_info(PutOperation::Update, 1, "", vec![ObjProperties::raw("Cargo", b"0")]);
+        update.expected_version = 4;
+        let add = PutObjCommand::from_info(PutOperation::Add, 2, "Ship2", vec![ObjProperties::raw("Cargo", b"100")]);
+
+        let cmd = TxnCommand::from_info(vec![update, add]);
+        let bytes = cmd.serialize();
+
+        let output = TxnCommand::deserialize(&bytes).unwrap();
+        assert_eq!(cmd, output);
+
+        TxnCommand::deserialize(&bytes[0..bytes.len() - 1]).expect_err("Buffer is truncated");
+    }
+
+    #[test]
+    fn test_txn_reply_roundtrip() {
+        let reply = TxnReply::from_info(42, vec![(1, 5), (2, 1)]);
+
+        assert_eq!(TxnReply::deserialize(&reply.serialize()).unwrap(), reply);
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+use std::cmp::Ordering;
+use std::io::{BufReader, Read};
+use crate::utils::*;
+
+// Deepest nesting of List values accepted from clients.
+pub static MAX_LIST_DEPTH: usize = 16;
+
+/**
+ * Type of a property value, declared by the class of the object. Only the
+ * items of List values are sent with their type.
+ *
+ * Raw is the untyped legacy format, its data is never looked at by the
+ * server. Bytes is an opaque blob the client explicitly declared as such.
+ */
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ValueType {
+    Raw,
+    Bool,
+    Int,
+    Float,
+    Vec2,
+    Vec3,
+    Str,
+    Bytes,
+    List
+}
+
+pub fn u32_to_value_type(value: u32) -> Result<ValueType, String> {
+    match value {
+        0 => Ok(ValueType::Raw),
+        1 => Ok(ValueType::Bool),
+        2 => Ok(ValueType::Int),
+        3 => Ok(ValueType::Float),
+        4 => Ok(ValueType::Vec2),
+        5 => Ok(ValueType::Vec3),
+        6 => Ok(ValueType::Str),
+        7 => Ok(ValueType::Bytes),
+        8 => Ok(ValueType::List),
+        _ => Err(format!("Unknown value type {}", value))
+    }
+}
+
+pub fn value_type_to_u32(value_type: ValueType) -> u32 {
+    match value_type {
+        ValueType::Raw => 0,
+        ValueType::Bool => 1,
+        ValueType::Int => 2,
+        ValueType::Float => 3,
+        ValueType::Vec2 => 4,
+        ValueType::Vec3 => 5,
+        ValueType::Str => 6,
+        ValueType::Bytes => 7,
+        ValueType::List => 8
+    }
+}
+
+/**
+ * A decoded property value.
+ *
+ * Data layout of each type :
+ *
+ * Raw, Bytes : the bytes as is
+ * Bool : 1 byte, 0 or 1
+ * Int : 8 bytes, little endian i64
+ * Float : 8 bytes, little endian f64
+ * Vec2, Vec3 : 2 or 3 little endian f64
+ * Str : UTF8 bytes, not null terminated
+ * List : [count:4] then for each item [type:4][length:4][data], lists can
+ *        be nested at most MAX_LIST_DEPTH deep
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyValue {
+    Raw(Vec<u8>),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Vec2(f64, f64),
+    Vec3(f64, f64, f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<PropertyValue>)
+}
+
+impl PropertyValue {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            PropertyValue::Raw(_) => ValueType::Raw,
+            PropertyValue::Bool(_) => ValueType::Bool,
+            PropertyValue::Int(_) => ValueType::Int,
+            PropertyValue::Float(_) => ValueType::Float,
+            PropertyValue::Vec2(_, _) => ValueType::Vec2,
+            PropertyValue::Vec3(_, _, _) => ValueType::Vec3,
+            PropertyValue::Str(_) => ValueType::Str,
+            PropertyValue::Bytes(_) => ValueType::Bytes,
+            PropertyValue::List(_) => ValueType::List
+        }
+    }
+
+    /**
+     * Encode the value into the data of a property.
+     */
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            PropertyValue::Raw(data) | PropertyValue::Bytes(data) => data.clone(),
+            PropertyValue::Bool(value) => vec![*value as u8],
+            PropertyValue::Int(value) => u64_to_buf(*value as u64),
+            PropertyValue::Float(value) => f64_to_buf(*value),
+            PropertyValue::Vec2(x, y) => [f64_to_buf(*x), f64_to_buf(*y)].concat(),
+            PropertyValue::Vec3(x, y, z) => [f64_to_buf(*x), f64_to_buf(*y), f64_to_buf(*z)].concat(),
+            PropertyValue::Str(value) => value.as_bytes().to_vec(),
+            PropertyValue::List(items) => {
+                let mut result = u32_to_buf(usize_to_u32(items.len()));
+
+                for item in items {
+                    result.append(&mut item.encode_item());
+                }
+
+                result
+            }
+        }
+    }
+
+    /**
+     * Encode the value as an item of a list, with its type.
+     *
+     * Wire format : [type:4][length:4][data]
+     */
+    pub fn encode_item(&self) -> Vec<u8> {
+        let data = self.encode();
+
+        [u32_to_buf(value_type_to_u32(self.value_type())), u32_to_buf(usize_to_u32(data.len())), data].concat()
+    }
+
+    /**
+     * Decode a value encoded by encode_item.
+     */
+    pub fn decode_item(data: &[u8]) -> Result<PropertyValue, String> {
+        let mut reader = data;
+        let item = read_item(&mut reader, data.len(), 0)?;
+
+        result_from_condition(reader.is_empty(), String::from("List item has trailing bytes"))?;
+
+        Ok(item)
+    }
+
+    /**
+     * Decode the data of a property of the given type. The data must have
+     * exactly the size expected by the type.
+     */
+    pub fn decode(value_type: ValueType, data: &[u8]) -> Result<PropertyValue, String> {
+        PropertyValue::decode_nested(value_type, data, 0)
+    }
+
+    fn decode_nested(value_type: ValueType, data: &[u8], depth: usize) -> Result<PropertyValue, String> {
+        let expect_len = |len: usize| {
+            result_from_condition(data.len() == len, format!("{:?} value must be {} bytes, got {}", value_type, len, data.len()))
+        };
+
+        let value = match value_type {
+            ValueType::Raw => PropertyValue::Raw(data.to_vec()),
+            ValueType::Bytes => PropertyValue::Bytes(data.to_vec()),
+            ValueType::Bool => {
+                expect_len(1)?;
+                result_from_condition(data[0] <= 1, String::from("Bool value must be 0 or 1"))?;
+
+                PropertyValue::Bool(data[0] == 1)
+            },
+            ValueType::Int => {
+                expect_len(8)?;
+
+                PropertyValue::Int(read_u64_field(&mut &data[..])? as i64)
+            },
+            ValueType::Float => {
+                expect_len(8)?;
+
+                PropertyValue::Float(read_f64_field(&mut &data[..])?)
+            },
+            ValueType::Vec2 => {
+                expect_len(16)?;
+                let mut reader = data;
+
+                PropertyValue::Vec2(read_f64_field(&mut reader)?, read_f64_field(&mut reader)?)
+            },
+            ValueType::Vec3 => {
+                expect_len(24)?;
+                let mut reader = data;
+
+                PropertyValue::Vec3(read_f64_field(&mut reader)?, read_f64_field(&mut reader)?, read_f64_field(&mut reader)?)
+            },
+            ValueType::Str => {
+                PropertyValue::Str(String::from_utf8(data.to_vec()).map_err(|_| "Str value is not UTF8.")?)
+            },
+            ValueType::List => PropertyValue::List(decode_list(data, depth)?)
+        };
+
+        Ok(value)
+    }
+
+    /**
+     * Interpolate between two values, `ratio` going from 0 for this value to
+     * 1 for the other one. Only Float, Vec2 and Vec3 values of the same type
+     * can be interpolated.
+     */
+    pub fn interpolate(&self, other: &PropertyValue, ratio: f64) -> Option<PropertyValue> {
+        let lerp = |a: f64, b: f64| a + (b - a) * ratio;
+
+        match (self, other) {
+            (PropertyValue::Float(a), PropertyValue::Float(b)) => Some(PropertyValue::Float(lerp(*a, *b))),
+            (PropertyValue::Vec2(ax, ay), PropertyValue::Vec2(bx, by)) => {
+                Some(PropertyValue::Vec2(lerp(*ax, *bx), lerp(*ay, *by)))
+            },
+            (PropertyValue::Vec3(ax, ay, az), PropertyValue::Vec3(bx, by, bz)) => {
+                Some(PropertyValue::Vec3(lerp(*ax, *bx), lerp(*ay, *by), lerp(*az, *bz)))
+            },
+            _ => None
+        }
+    }
+
+    /**
+     * Read the value as a number, used to compare values of different
+     * numeric types.
+     */
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PropertyValue::Int(value) => Some(*value as f64),
+            PropertyValue::Float(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    /**
+     * Add a delta to a number, None when the types differ or the result
+     * overflows.
+     */
+    pub fn add(&self, delta: &PropertyValue) -> Option<PropertyValue> {
+        match (self, delta) {
+            (PropertyValue::Int(a), PropertyValue::Int(b)) => a.checked_add(*b).map(PropertyValue::Int),
+            (PropertyValue::Float(a), PropertyValue::Float(b)) => Some(PropertyValue::Float(a + b)),
+            _ => None
+        }
+    }
+
+    /**
+     * Subtract a delta from a number, None when the types differ or the
+     * result overflows.
+     */
+    pub fn subtract(&self, delta: &PropertyValue) -> Option<PropertyValue> {
+        match (self, delta) {
+            (PropertyValue::Int(a), PropertyValue::Int(b)) => a.checked_sub(*b).map(PropertyValue::Int),
+            (PropertyValue::Float(a), PropertyValue::Float(b)) => Some(PropertyValue::Float(a - b)),
+            _ => None
+        }
+    }
+
+    /**
+     * Order two values. Numbers are compared with each other whatever their
+     * type, strings by their bytes. Other values cannot be ordered.
+     */
+    pub fn compare(&self, other: &PropertyValue) -> Option<Ordering> {
+        match (self, other) {
+            (PropertyValue::Int(a), PropertyValue::Int(b)) => Some(a.cmp(b)),
+            (PropertyValue::Str(a), PropertyValue::Str(b)) => Some(a.cmp(b)),
+            _ => self.as_f64()?.partial_cmp(&other.as_f64()?)
+        }
+    }
+}
+
+fn decode_list(data: &[u8], depth: usize) -> Result<Vec<PropertyValue>, String> {
+    result_from_condition(depth < MAX_LIST_DEPTH, format!("Lists cannot be nested more than {} deep", MAX_LIST_DEPTH))?;
+
+    let mut reader = BufReader::new(data);
+
+    let mut count_buf = [0; 4];
+    reader.read_exact(&mut count_buf).map_err(|_| "Unable to read list length.")?;
+
+    let mut items = vec![];
+    for _i in 0..buf_to_u32(count_buf) {
+        items.push(read_item(&mut reader, data.len(), depth + 1)?);
+    }
+
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest).map_err(|_| "Unable to read list.")?;
+    result_from_condition(rest.is_empty(), String::from("List value has trailing bytes"))?;
+
+    Ok(items)
+}
+
+/**
+ * Read a list item, at most `max_len` bytes long.
+ */
+fn read_item<R: Read>(reader: &mut R, max_len: usize, depth: usize) -> Result<PropertyValue, String> {
+    let mut type_buf = [0; 4];
+    reader.read_exact(&mut type_buf).map_err(|_| "Unable to read list item type.")?;
+
+    let mut length_buf = [0; 4];
+    reader.read_exact(&mut length_buf).map_err(|_| "Unable to read list item length.")?;
+
+    let item_len = u32_to_usize(buf_to_u32(length_buf));
+    result_from_condition(item_len <= max_len, String::from("List item is longer than the list"))?;
+
+    let mut item_data = vec![0; item_len];
+    reader.read_exact(&mut item_data).map_err(|_| "Unable to read list item data.")?;
+
+    PropertyValue::decode_nested(u32_to_value_type(buf_to_u32(type_buf))?, &item_data, depth)
+}
+
+/**
+ * Readable form of the values, used in logs and admin dumps.
+ */
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyValue::Raw(data) => write!(f, "raw[{} bytes]", data.len()),
+            PropertyValue::Bytes(data) => write!(f, "bytes[{} bytes]", data.len()),
+            PropertyValue::Bool(value) => write!(f, "{}", value),
+            PropertyValue::Int(value) => write!(f, "{}", value),
+            PropertyValue::Float(value) => write!(f, "{}", value),
+            PropertyValue::Vec2(x, y) => write!(f, "({}, {})", x, y),
+            PropertyValue::Vec3(x, y, z) => write!(f, "({}, {}, {})", x, y, z),
+            PropertyValue::Str(value) => write!(f, "\"{}\"", value),
+            PropertyValue::List(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_roundtrip() {
+        let values = vec![
+            PropertyValue::Raw(b"{ x: 5 }".to_vec()),
+            PropertyValue::Bool(true),
+            PropertyValue::Int(-42),
+            PropertyValue::Float(3.5),
+            PropertyValue::Vec2(1.0, -2.0),
+            PropertyValue::Vec3(1.0, 2.0, 3.0),
+            PropertyValue::Str(String::from("Black Pearl")),
+            PropertyValue::Bytes(vec![0, 1, 2]),
+            PropertyValue::List(vec![PropertyValue::Int(1), PropertyValue::Str(String::from("Rum"))])
+        ];
+
+        for value in values {
+            let decoded = PropertyValue::decode(value.value_type(), &value.encode()).unwrap();
+            assert_eq!(value, decoded);
+        }
+    }
+
+    #[test]
+    fn test_value_validation() {
+        PropertyValue::decode(ValueType::Int, &[1, 2, 3]).expect_err("Int needs 8 bytes");
+        PropertyValue::decode(ValueType::Bool, &[2]).expect_err("Bool is 0 or 1");
+        PropertyValue::decode(ValueType::Str, &[0xFF, 0xFE]).expect_err("Str is not UTF8");
+
+        let mut list = PropertyValue::List(vec![PropertyValue::Int(1)]).encode();
+        list.push(0);
+        PropertyValue::decode(ValueType::List, &list).expect_err("List has trailing bytes");
+
+        let mut nested = PropertyValue::List(vec![]);
+        for _i in 0..MAX_LIST_DEPTH {
+            nested = PropertyValue::List(vec![nested]);
+        }
+        PropertyValue::decode(ValueType::List, &nested.encode()).expect_err("List is nested too deep");
+
+        let item = PropertyValue::Str(String::from("Rum"));
+        assert_eq!(PropertyValue::decode_item(&item.encode_item()), Ok(item));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let a = PropertyValue::Vec2(0.0, 10.0);
+        let b = PropertyValue::Vec2(10.0, 20.0);
+
+        assert_eq!(a.interpolate(&b, 0.25), Some(PropertyValue::Vec2(2.5, 12.5)));
+        assert_eq!(PropertyValue::Int(1).interpolate(&PropertyValue::Int(3), 0.5), None);
+        assert_eq!(a.interpolate(&PropertyValue::Float(1.0), 0.5), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(PropertyValue::Int(5).add(&PropertyValue::Int(-7)), Some(PropertyValue::Int(-2)));
+        assert_eq!(PropertyValue::Float(1.5).subtract(&PropertyValue::Float(0.5)), Some(PropertyValue::Float(1.0)));
+        assert_eq!(PropertyValue::Int(i64::MAX).add(&PropertyValue::Int(1)), None);
+        assert_eq!(PropertyValue::Int(1).add(&PropertyValue::Float(1.0)), None);
+
+        assert_eq!(PropertyValue::Int(2).compare(&PropertyValue::Float(2.5)), Some(Ordering::Less));
+        assert_eq!(PropertyValue::Str(String::from("b")).compare(&PropertyValue::Str(String::from("a"))), Some(Ordering::Greater));
+        assert_eq!(PropertyValue::Bool(true).compare(&PropertyValue::Bool(false)), None);
+    }
+
+    #[test]
+    fn test_value_display() {
+        let value = PropertyValue::List(vec![PropertyValue::Vec2(1.0, 2.5), PropertyValue::Bool(false)]);
+
+        assert_eq!(value.to_string(), "[(1, 2.5), false]");
+    }
+}
@@ -0,0 +1,745 @@
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use crate::commands::{JoinMode, GamePhase};
+use crate::objects::Viewer;
+
+// Characters used in invite codes. Characters that look alike such as 0/O and
+// 1/I are left out so codes can be read aloud or typed easily.
+static INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+static INVITE_CODE_LENGTH: usize = 6;
+
+/**
+ * A game session running on the server.
+ *
+ * The host is the client that created the session. Every object is owned by
+ * the player who added it, and can be written by its owner and by the host.
+ * The host can also grant write rights on a specific object to another
+ * player, and the owner or the host can transfer the object to another
+ * player, see AUTH. When its owner leaves, an object is either given back to
+ * the host or deleted, as chosen when it was added.
+ *
+ * Spectators watch the session without taking a player slot. They can read
+ * the session but never write to it.
+ *
+ * A session goes through the Lobby, Countdown, Running and Finished phases.
+ * In the lobby players toggle a ready flag and the host starts the countdown
+ * once every other player is ready. The host is considered always ready.
+ */
+#[derive(Clone, Debug)]
+pub struct GameSession {
+    pub name: String,
+    pub password: String,
+    pub host: String,
+    pub players: Vec<String>,
+    pub spectators: Vec<String>,
+    // Maximum number of players, 0 when there is no limit.
+    pub max_players: u64,
+    // Seconds object updates are held back before reaching spectators.
+    pub spectator_delay: u64,
+    // Object ids each player was allowed to write by the host.
+    pub write_grants: HashMap<String, Vec<u64>>,
+    // Team of each player put on one by the host.
+    pub teams: HashMap<String, String>,
+    pub phase: GamePhase,
+    pub ready: Vec<String>,
+    // Time at which the game starts running, set when the countdown starts.
+    pub start_at: Option<DateTime<UTC>>,
+    pub bans: Vec<Ban>
+}
+
+/**
+ * A player banned from a game session. The player cannot join again using
+ * the same name nor from the same connection address.
+ */
+#[derive(Clone, Debug)]
+pub struct Ban {
+    pub player: String,
+    pub address: Option<IpAddr>
+}
+
+impl GameSession {
+    pub fn new(name: &str, password: &str, host: &str) -> GameSession {
+        GameSession {
+            name: String::from(name),
+            password: String::from(password),
+            host: String::from(host),
+            players: vec![String::from(host)],
+            spectators: vec![],
+            max_players: 0,
+            spectator_delay: 0,
+            write_grants: HashMap::new(),
+            teams: HashMap::new(),
+            phase: GamePhase::Lobby,
+            ready: vec![],
+            start_at: None,
+            bans: vec![]
+        }
+    }
+
+    pub fn is_host(&self, player: &str) -> bool {
+        self.host == player
+    }
+
+    pub fn has_player(&self, player: &str) -> bool {
+        self.players.iter().any(|p| p == player)
+    }
+
+    pub fn is_spectator(&self, player: &str) -> bool {
+        self.spectators.iter().any(|p| p == player)
+    }
+
+    /**
+     * Checks if a client is part of the session, either as a player or as a
+     * spectator.
+     */
+    pub fn has_member(&self, player: &str) -> bool {
+        self.has_player(player) || self.is_spectator(player)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.max_players != 0 && self.players.len() as u64 >= self.max_players
+    }
+
+    /**
+     * Remove a player or a spectator from the session, along with its ready
+     * flag, write grants and team. The host cannot be removed.
+     */
+    pub fn remove_member(&mut self, player: &str) -> Result<(), String> {
+        if self.is_host(player) {
+            return Err(String::from("The host cannot be removed from its own game session"));
+        }
+
+        if !self.has_member(player) {
+            return Err(format!("Player {} is not in game session {}", player, self.name));
+        }
+
+        self.players.retain(|p| p != player);
+        self.spectators.retain(|p| p != player);
+        self.ready.retain(|p| p != player);
+        self.write_grants.remove(player);
+        self.teams.remove(player);
+
+        Ok(())
+    }
+
+    pub fn ban(&mut self, player: &str, address: Option<IpAddr>) {
+        self.bans.push(Ban {
+            player: String::from(player),
+            address
+        });
+    }
+
+    pub fn is_banned(&self, player: &str, address: Option<IpAddr>) -> bool {
+        self.bans.iter().any(|ban| {
+            ban.player == player || (ban.address.is_some() && ban.address == address)
+        })
+    }
+
+    /**
+     * Set the ready flag of a player. Flags can only change in the lobby.
+     */
+    pub fn set_ready(&mut self, player: &str, ready: bool) -> Result<(), String> {
+        if !self.has_player(player) {
+            return Err(format!("Player {} is not in game session {}", player, self.name));
+        }
+
+        if self.phase != GamePhase::Lobby {
+            return Err(format!("Game session {} is not in the lobby", self.name));
+        }
+
+        self.ready.retain(|p| p != player);
+
+        if ready {
+            self.ready.push(String::from(player));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_ready(&self, player: &str) -> bool {
+        self.is_host(player) || self.ready.iter().any(|p| p == player)
+    }
+
+    pub fn all_ready(&self) -> bool {
+        self.players.iter().all(|p| self.is_ready(p))
+    }
+
+    /**
+     * Move the session to another phase. Only the following transitions are
+     * allowed :
+     *
+     * Lobby -> Countdown, once all players are ready
+     * Countdown -> Lobby, to cancel the countdown
+     * Countdown -> Running, once the start time is reached
+     * Running -> Finished
+     *
+     * Asking for Running right after the countdown ended succeeds.
+     */
+    pub fn transition(&mut self, target: GamePhase, countdown: chrono::Duration, now: DateTime<UTC>) -> Result<(), String> {
+        if self.update_phase(now) && target == GamePhase::Running {
+            return Ok(());
+        }
+
+        match (self.phase, target) {
+            (GamePhase::Lobby, GamePhase::Countdown) => {
+                if !self.all_ready() {
+                    return Err(format!("Not all players of {} are ready", self.name));
+                }
+
+                let start_at = now.checked_add_signed(countdown).ok_or_else(|| String::from("Countdown is too long"))?;
+                self.start_at = Some(start_at);
+            },
+            (GamePhase::Countdown, GamePhase::Lobby) => {
+                self.start_at = None;
+            },
+            (GamePhase::Countdown, GamePhase::Running) => {
+                return Err(format!("Game session {} has not reached its start time", self.name));
+            },
+            (GamePhase::Running, GamePhase::Finished) => {},
+            (from, to) => {
+                return Err(format!("Game session {} cannot go from {:?} to {:?}", self.name, from, to));
+            }
+        }
+
+        self.phase = target;
+
+        Ok(())
+    }
+
+    /**
+     * Move a session in countdown to the running phase once its start time is
+     * reached. Returns true if the phase changed.
+     */
+    pub fn update_phase(&mut self, now: DateTime<UTC>) -> bool {
+        match (self.phase, self.start_at) {
+            (GamePhase::Countdown, Some(start_at)) if now >= start_at => {
+                self.phase = GamePhase::Running;
+
+                true
+            },
+            _ => false
+        }
+    }
+
+    /**
+     * Start time of the game in unix milliseconds, 0 if the countdown did not
+     * start.
+     */
+    pub fn start_timestamp_millis(&self) -> u64 {
+        match self.start_at {
+            Some(start_at) => start_at.timestamp() as u64 * 1000 + start_at.timestamp_subsec_millis() as u64,
+            None => 0
+        }
+    }
+
+    /**
+     * Allow a player of the session to write an object.
+     */
+    pub fn grant_write(&mut self, player: &str, object_id: u64) -> Result<(), String> {
+        if !self.has_player(player) {
+            return Err(format!("Player {} is not in game session {}", player, self.name));
+        }
+
+        let grants = self.write_grants.entry(String::from(player)).or_default();
+
+        if !grants.contains(&object_id) {
+            grants.push(object_id);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Checks if a player is allowed to write an object. The host can write
+     * everything, the other players can only write the objects they were
+     * granted.
+     */
+    pub fn can_write(&self, player: &str, object_id: u64) -> bool {
+        if self.is_host(player) {
+            return true;
+        }
+
+        match self.write_grants.get(player) {
+            Some(grants) => grants.contains(&object_id),
+            None => false
+        }
+    }
+
+    /**
+     * Put a player of the session on a team, an empty team takes it off its
+     * current one.
+     */
+    pub fn set_team(&mut self, player: &str, team: &str) -> Result<(), String> {
+        if !self.has_player(player) {
+            return Err(format!("Player {} is not in game session {}", player, self.name));
+        }
+
+        if team.is_empty() {
+            self.teams.remove(player);
+        } else {
+            self.teams.insert(String::from(player), String::from(team));
+        }
+
+        Ok(())
+    }
+
+    pub fn team_of(&self, player: &str) -> Option<&str> {
+        self.teams.get(player).map(|t| t.as_str())
+    }
+
+    /**
+     * Describe what a member of the session is allowed to see of the objects
+     * sent to it, and when. The host sees every property, spectators see
+     * everything late.
+     */
+    pub fn viewer(&self, player: &str) -> Viewer {
+        let team = match self.team_of(player) {
+            Some(team) => self.teams.iter().filter(|(_, t)| t.as_str() == team).map(|(p, _)| p.clone()).collect(),
+            None => vec![]
+        };
+
+        Viewer {
+            player: String::from(player),
+            team,
+            sees_all: self.is_host(player),
+            delay: if self.is_spectator(player) { self.spectator_delay } else { 0 }
+        }
+    }
+}
+
+/**
+ * A short code giving access to a game session without its password.
+ */
+#[derive(Clone, Debug)]
+pub struct InviteCode {
+    pub code: String,
+    pub game: String,
+    pub expires_at: DateTime<UTC>,
+    // Remaining number of joins, None when the code can be used forever.
+    pub uses_left: Option<u64>
+}
+
+impl InviteCode {
+    pub fn is_expired(&self, now: DateTime<UTC>) -> bool {
+        now >= self.expires_at || self.uses_left == Some(0)
+    }
+}
+
+pub struct GameManager {
+    games: HashMap<String, GameSession>,
+    invites: HashMap<String, InviteCode>,
+    invite_seed: u64
+}
+
+impl GameManager {
+    /**
+     * Create a new game session list.
+     */
+    pub fn new() -> GameManager {
+        GameManager {
+            games: HashMap::new(),
+            invites: HashMap::new(),
+            invite_seed: UTC::now().timestamp_subsec_nanos() as u64 | 1
+        }
+    }
+
+    /**
+     * Create a game session hosted by a player. Session names are unique.
+     *
+     * The created session is returned so the caller can set its options.
+     */
+    pub fn create_game(&mut self, name: &str, password: &str, host: &str) -> Result<&mut GameSession, String> {
+        if self.games.contains_key(name) {
+            return Err(format!("Game session {} already exists", name));
+        }
+
+        let game = self.games.entry(String::from(name)).or_insert_with(|| GameSession::new(name, password, host));
+
+        Ok(game)
+    }
+
+    /**
+     * Add a player or a spectator to an existing game session. Spectators
+     * are not counted against the capacity of the session.
+     */
+    pub fn join_game(&mut self, name: &str, password: &str, player: &str, address: Option<IpAddr>, mode: JoinMode) -> Result<(), String> {
+        let game = self.games.get_mut(name).ok_or(format!("Game session {} does not exist", name))?;
+
+        if game.password != password {
+            return Err(format!("Wrong password for game session {}", name));
+        }
+
+        GameManager::add_member(game, player, address, mode)
+    }
+
+    /**
+     * Add a player or a spectator to the game session an invite code was
+     * created for. Returns the name of the session that was joined.
+     */
+    pub fn join_game_with_invite(&mut self, code: &str, player: &str, address: Option<IpAddr>, mode: JoinMode, now: DateTime<UTC>) -> Result<String, String> {
+        self.remove_expired_invites(now);
+
+        let invite = self.invites.get_mut(code).ok_or(format!("Invite code {} is not valid", code))?;
+        let game = self.games.get_mut(&invite.game).ok_or(format!("Game session {} does not exist", invite.game))?;
+
+        GameManager::add_member(game, player, address, mode)?;
+
+        if let Some(uses) = invite.uses_left {
+            invite.uses_left = Some(uses - 1);
+        }
+
+        Ok(game.name.clone())
+    }
+
+    /**
+     * Create an invite code for a game session. A max_uses of 0 means the
+     * code can be used until it expires.
+     */
+    pub fn create_invite(&mut self, game: &str, lifetime: chrono::Duration, max_uses: u64, now: DateTime<UTC>) -> Result<InviteCode, String> {
+        if !self.games.contains_key(game) {
+            return Err(format!("Game session {} does not exist", game));
+        }
+
+        let expires_at = now.checked_add_signed(lifetime).ok_or_else(|| String::from("Invite lifetime is too long"))?;
+
+        self.remove_expired_invites(now);
+
+        let mut code = self.next_invite_code();
+        while self.invites.contains_key(&code) {
+            code = self.next_invite_code();
+        }
+
+        let invite = InviteCode {
+            code: code.clone(),
+            game: String::from(game),
+            expires_at,
+            uses_left: if max_uses == 0 { None } else { Some(max_uses) }
+        };
+
+        self.invites.insert(code, invite.clone());
+
+        Ok(invite)
+    }
+
+    /**
+     * Revoke an invite code. The code must belong to the given game session
+     * so a host cannot revoke the codes of another session.
+     */
+    pub fn revoke_invite(&mut self, game: &str, code: &str) -> Result<(), String> {
+        match self.invites.get(code) {
+            Some(invite) if invite.game == game => {
+                self.invites.remove(code);
+
+                Ok(())
+            },
+            _ => Err(format!("Invite code {} does not exist", code))
+        }
+    }
+
+    fn remove_expired_invites(&mut self, now: DateTime<UTC>) {
+        self.invites.retain(|_, invite| !invite.is_expired(now));
+    }
+
+    /**
+     * Generate a new code using a xorshift generator. Codes only need to be
+     * hard to guess by accident, not cryptographically secure.
+     */
+    fn next_invite_code(&mut self) -> String {
+        let mut code = String::new();
+
+        for _i in 0..INVITE_CODE_LENGTH {
+            self.invite_seed ^= self.invite_seed << 13;
+            self.invite_seed ^= self.invite_seed >> 7;
+            self.invite_seed ^= self.invite_seed << 17;
+
+            let index = (self.invite_seed % INVITE_ALPHABET.len() as u64) as usize;
+            code.push(INVITE_ALPHABET[index] as char);
+        }
+
+        code
+    }
+
+    fn add_member(game: &mut GameSession, player: &str, address: Option<IpAddr>, mode: JoinMode) -> Result<(), String> {
+        if game.is_banned(player, address) {
+            return Err(format!("Player {} is banned from game session {}", player, game.name));
+        }
+
+        if game.has_member(player) {
+            return Err(format!("Player {} is already in game session {}", player, game.name));
+        }
+
+        match mode {
+            JoinMode::Player => {
+                if game.is_full() {
+                    return Err(format!("Game session {} is full", game.name));
+                }
+
+                game.players.push(String::from(player));
+            },
+            JoinMode::Spectator => {
+                game.spectators.push(String::from(player));
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Move the sessions whose countdown ended to the running phase. Returns
+     * the names of the sessions that started.
+     */
+    pub fn update_phases(&mut self, now: DateTime<UTC>) -> Vec<String> {
+        self.games.values_mut()
+                  .filter_map(|game| if game.update_phase(now) { Some(game.name.clone()) } else { None })
+                  .collect()
+    }
+
+    /**
+     * Checks if a player is the host of a game session.
+     */
+    pub fn is_host_of(&self, game: &str, player: &str) -> bool {
+        self.games.get(game).is_some_and(|game| game.is_host(player))
+    }
+
+    pub fn get_game(&self, name: &str) -> Option<&GameSession> {
+        self.games.get(name)
+    }
+
+    pub fn get_game_mut(&mut self, name: &str) -> Option<&mut GameSession> {
+        self.games.get_mut(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_join() {
+        let mut manager = GameManager::new();
+
+        manager.create_game("Caribbean", "Rum", "Host").unwrap();
+        manager.create_game("Caribbean", "", "Other").expect_err("Session name is taken");
+
+        manager.join_game("Caribbean", "Water", "Player", None, JoinMode::Player).expect_err("Password is wrong");
+        manager.join_game("Atlantic", "", "Player", None, JoinMode::Player).expect_err("Session does not exist");
+        manager.join_game("Caribbean", "Rum", "Player", None, JoinMode::Player).unwrap();
+        manager.join_game("Caribbean", "Rum", "Host", None, JoinMode::Player).expect_err("Host is already a member");
+        manager.join_game("Caribbean", "Rum", "Player", None, JoinMode::Spectator).expect_err("Player is already a member");
+
+        let game = manager.get_game("Caribbean").unwrap();
+        assert!(game.is_host("Host"));
+        assert!(game.has_player("Player"));
+        assert!(!game.is_host("Player"));
+    }
+
+    #[test]
+    fn test_spectators() {
+        let mut manager = GameManager::new();
+
+        manager.create_game("Caribbean", "", "Host").unwrap().max_players = 2;
+        manager.join_game("Caribbean", "", "Player", None, JoinMode::Player).unwrap();
+
+        // The session is full for players but not for spectators.
+        manager.join_game("Caribbean", "", "Late", None, JoinMode::Player).expect_err("Session is full");
+        manager.join_game("Caribbean", "", "Watcher", None, JoinMode::Spectator).unwrap();
+
+        let game = manager.get_game_mut("Caribbean").unwrap();
+        assert!(game.is_spectator("Watcher"));
+        assert!(game.has_member("Watcher"));
+        assert!(!game.has_player("Watcher"));
+        assert_eq!(game.players.len(), 2);
+
+        // Spectators cannot be granted write rights.
+        game.grant_write("Watcher", 1).expect_err("Spectators cannot write");
+        assert!(!game.can_write("Watcher", 1));
+    }
+
+    #[test]
+    fn test_invite_codes() {
+        let mut manager = GameManager::new();
+        manager.create_game("Caribbean", "Rum", "Host").unwrap();
+
+        let now = UTC::now();
+        let invite = manager.create_invite("Caribbean", chrono::Duration::seconds(60), 2, now).unwrap();
+        assert_eq!(invite.code.len(), INVITE_CODE_LENGTH);
+
+        // The code replaces the password, and is used up after two joins.
+        let joined = manager.join_game_with_invite(&invite.code, "One", None, JoinMode::Player, now).unwrap();
+        assert_eq!(joined, "Caribbean");
+        manager.join_game_with_invite(&invite.code, "Two", None, JoinMode::Spectator, now).unwrap();
+        manager.join_game_with_invite(&invite.code, "Three", None, JoinMode::Player, now).expect_err("Code is used up");
+
+        // Codes expire after their lifetime.
+        let invite = manager.create_invite("Caribbean", chrono::Duration::seconds(60), 0, now).unwrap();
+        let later = now + chrono::Duration::seconds(61);
+        manager.join_game_with_invite(&invite.code, "Three", None, JoinMode::Player, later).expect_err("Code expired");
+
+        // Codes can only be revoked by their own session.
+        let invite = manager.create_invite("Caribbean", chrono::Duration::seconds(60), 0, now).unwrap();
+        manager.revoke_invite("Atlantic", &invite.code).expect_err("Code belongs to another session");
+        manager.revoke_invite("Caribbean", &invite.code).unwrap();
+        manager.join_game_with_invite(&invite.code, "Three", None, JoinMode::Player, now).expect_err("Code was revoked");
+
+        manager.create_invite("Atlantic", chrono::Duration::seconds(60), 0, now).expect_err("Session does not exist");
+        manager.create_invite("Caribbean", chrono::Duration::max_value(), 0, now).expect_err("Lifetime overflows the date");
+    }
+
+    #[test]
+    fn test_lobby_phases() {
+        let mut game = GameSession::new("Caribbean", "", "Host");
+        game.players.push(String::from("Player"));
+        game.spectators.push(String::from("Watcher"));
+
+        let now = UTC::now();
+        let countdown = chrono::Duration::seconds(5);
+
+        // Cannot start until every player is ready, spectators don't count.
+        game.transition(GamePhase::Countdown, countdown, now).expect_err("Player is not ready");
+        game.set_ready("Watcher", true).expect_err("Spectators have no ready flag");
+        game.set_ready("Player", true).unwrap();
+        assert!(game.all_ready());
+
+        game.transition(GamePhase::Countdown, countdown, now).unwrap();
+        assert_eq!(game.start_at, Some(now + countdown));
+        game.set_ready("Player", false).expect_err("Not in the lobby anymore");
+        game.transition(GamePhase::Finished, countdown, now).expect_err("Game is not running");
+        game.transition(GamePhase::Running, countdown, now).expect_err("Start time not reached");
+
+        // Running once the start time is reached.
+        assert!(!game.update_phase(now));
+        assert!(game.update_phase(now + countdown));
+        assert_eq!(game.phase, GamePhase::Running);
+
+        game.transition(GamePhase::Lobby, countdown, now).expect_err("Game is running");
+        game.transition(GamePhase::Finished, countdown, now).unwrap();
+        game.transition(GamePhase::Running, countdown, now).expect_err("Game is finished");
+    }
+
+    #[test]
+    fn test_cancel_countdown() {
+        let mut game = GameSession::new("Caribbean", "", "Host");
+
+        let now = UTC::now();
+        game.transition(GamePhase::Countdown, chrono::Duration::seconds(5), now).unwrap();
+        game.transition(GamePhase::Lobby, chrono::Duration::seconds(0), now).unwrap();
+
+        assert_eq!(game.start_at, None);
+        assert_eq!(game.start_timestamp_millis(), 0);
+        assert!(!game.update_phase(now + chrono::Duration::seconds(10)));
+
+        game.transition(GamePhase::Countdown, chrono::Duration::max_value(), now).expect_err("Start time overflows the date");
+        assert_eq!(game.phase, GamePhase::Lobby);
+    }
+
+    #[test]
+    fn test_countdown_end() {
+        let mut manager = GameManager::new();
+        manager.create_game("Caribbean", "", "Host").unwrap();
+        manager.create_game("Atlantic", "", "Other").unwrap();
+
+        let now = UTC::now();
+        let countdown = chrono::Duration::seconds(5);
+        manager.get_game_mut("Caribbean").unwrap().transition(GamePhase::Countdown, countdown, now).unwrap();
+
+        assert!(manager.update_phases(now).is_empty());
+        assert_eq!(manager.update_phases(now + countdown), vec![String::from("Caribbean")]);
+        assert!(manager.update_phases(now + countdown).is_empty());
+
+        // The host asking to run the game once the countdown ended is not an error.
+        let game = manager.get_game_mut("Atlantic").unwrap();
+        game.transition(GamePhase::Countdown, countdown, now).unwrap();
+        game.transition(GamePhase::Running, countdown, now + countdown).unwrap();
+        assert_eq!(game.phase, GamePhase::Running);
+    }
+
+    #[test]
+    fn test_kick_and_ban() {
+        let mut manager = GameManager::new();
+        manager.create_game("Caribbean", "", "Host").unwrap();
+
+        let address: Option<IpAddr> = Some("10.0.0.5".parse().unwrap());
+        manager.join_game("Caribbean", "", "Griefer", address, JoinMode::Player).unwrap();
+
+        let game = manager.get_game_mut("Caribbean").unwrap();
+        game.grant_write("Griefer", 1).unwrap();
+        game.remove_member("Host").expect_err("Host cannot be kicked");
+
+        // A kicked player loses its grants but can come back.
+        game.remove_member("Griefer").unwrap();
+        assert!(!game.has_member("Griefer"));
+        assert!(!game.can_write("Griefer", 1));
+        game.remove_member("Griefer").expect_err("Player already left");
+
+        manager.join_game("Caribbean", "", "Griefer", address, JoinMode::Player).unwrap();
+
+        // A banned player cannot come back under its name nor its address.
+        let game = manager.get_game_mut("Caribbean").unwrap();
+        game.remove_member("Griefer").unwrap();
+        game.ban("Griefer", address);
+
+        manager.join_game("Caribbean", "", "Griefer", None, JoinMode::Spectator).expect_err("Name is banned");
+        manager.join_game("Caribbean", "", "Alt", address, JoinMode::Player).expect_err("Address is banned");
+        manager.join_game("Caribbean", "", "Alt", None, JoinMode::Player).unwrap();
+
+        // Players can be banned after they left.
+        manager.get_game_mut("Caribbean").unwrap().ban("Lurker", None);
+        manager.join_game("Caribbean", "", "Lurker", None, JoinMode::Player).expect_err("Name is banned");
+    }
+
+    #[test]
+    fn test_write_authority() {
+        let mut game = GameSession::new("Caribbean", "", "Host");
+        game.players.push(String::from("Player"));
+
+        // Host can write anything, players nothing by default.
+        assert!(game.can_write("Host", 1));
+        assert!(!game.can_write("Player", 1));
+
+        game.grant_write("Player", 1).unwrap();
+        assert!(game.can_write("Player", 1));
+        assert!(!game.can_write("Player", 2));
+
+        game.grant_write("Stranger", 1).expect_err("Stranger is not in the session");
+        assert!(!game.can_write("Stranger", 1));
+    }
+
+    #[test]
+    fn test_teams() {
+        let mut game = GameSession::new("Caribbean", "", "Host");
+        game.players.push(String::from("Blackbeard"));
+        game.players.push(String::from("Calico Jack"));
+        game.players.push(String::from("Anne Bonny"));
+
+        game.set_team("Blackbeard", "Pirates").unwrap();
+        game.set_team("Calico Jack", "Pirates").unwrap();
+        game.set_team("Anne Bonny", "Navy").unwrap();
+        game.set_team("Stranger", "Pirates").expect_err("Stranger is not in the session");
+
+        let mut viewer = game.viewer("Blackbeard");
+        viewer.team.sort();
+        assert_eq!(viewer.team, vec![String::from("Blackbeard"), String::from("Calico Jack")]);
+        assert!(!viewer.sees_all);
+        assert!(game.viewer("Host").sees_all);
+
+        game.set_team("Anne Bonny", "").unwrap();
+        assert_eq!(game.team_of("Anne Bonny"), None);
+
+        game.remove_member("Calico Jack").unwrap();
+        assert_eq!(game.viewer("Blackbeard").team, vec![String::from("Blackbeard")]);
+    }
+
+    #[test]
+    fn test_spectator_delay() {
+        let mut game = GameSession::new("Caribbean", "", "Host");
+        game.players.push(String::from("Blackbeard"));
+        game.spectators.push(String::from("Parrot"));
+        game.spectator_delay = 30;
+
+        // Only spectators see the objects late, replies to reads included.
+        assert_eq!(game.viewer("Parrot").delay, 30);
+        assert_eq!(game.viewer("Blackbeard").delay, 0);
+        assert_eq!(game.viewer("Host").delay, 0);
+    }
+}
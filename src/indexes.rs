@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use crate::commands::{Comparison, IndexKind, ObjProperties, PropertyValue, ValueType};
+use crate::objects::ObjectId;
+
+/**
+ * A property value as stored in an index. Numbers of every type share the
+ * same keys so a filter on an Int finds Float values, and keys are ordered
+ * like PropertyValue::compare orders values : numbers, then strings.
+ *
+ * Vectors and lists are not indexed, their floats can be equal without
+ * having the same bytes.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IndexKey {
+    // Bits of the number ordered like the number, see number_key.
+    Number(u64),
+    Str(String),
+    // Bool, Raw and Bytes values, tagged by type since values of different
+    // types with the same data are not equal.
+    Other(u8, Vec<u8>)
+}
+
+impl IndexKey {
+    pub fn from_value(value: &PropertyValue) -> Option<IndexKey> {
+        match value {
+            PropertyValue::Int(_) | PropertyValue::Float(_) => value.as_f64().map(|n| IndexKey::Number(number_key(n))),
+            PropertyValue::Str(value) => Some(IndexKey::Str(value.clone())),
+            PropertyValue::Bool(value) => Some(IndexKey::Other(0, vec![*value as u8])),
+            PropertyValue::Raw(data) => Some(IndexKey::Other(1, data.clone())),
+            PropertyValue::Bytes(data) => Some(IndexKey::Other(2, data.clone())),
+            PropertyValue::Vec2(_, _) | PropertyValue::Vec3(_, _, _) | PropertyValue::List(_) => None
+        }
+    }
+
+    /**
+     * Key of a property whose data holds a value of a type.
+     */
+    pub fn from_property(property: &ObjProperties, value_type: ValueType) -> Option<IndexKey> {
+        property.value_as(value_type).ok().and_then(|v| IndexKey::from_value(&v))
+    }
+}
+
+/**
+ * Map a number to an integer with the same order. Positive numbers get their
+ * sign bit set and negative numbers have all their bits flipped. Both zeros
+ * have the same key since they compare equal.
+ */
+fn number_key(number: f64) -> u64 {
+    let number = if number.is_nan() { f64::NAN } else { number + 0.0 };
+    let bits = number.to_bits();
+
+    if bits >> 63 == 1 { !bits } else { bits | (1 << 63) }
+}
+
+/**
+ * Index of the values of a property for the objects of a class. Objects
+ * without the property or with a value that cannot be indexed are left out.
+ *
+ * Lookups may return objects that do not match the filter, for example large
+ * Int values sharing a key, so callers check the filter on every object
+ * returned. They never miss a matching object.
+ */
+#[derive(Clone, Debug)]
+pub struct PropertyIndex {
+    kind: IndexKind,
+    hashed: HashMap<IndexKey, BTreeSet<ObjectId>>,
+    ordered: BTreeMap<IndexKey, BTreeSet<ObjectId>>,
+    keys: HashMap<ObjectId, IndexKey>
+}
+
+impl PropertyIndex {
+    pub fn new(kind: IndexKind) -> PropertyIndex {
+        PropertyIndex {
+            kind,
+            hashed: HashMap::new(),
+            ordered: BTreeMap::new(),
+            keys: HashMap::new()
+        }
+    }
+
+    /**
+     * Set the key of an object, replacing its previous one. A key of None
+     * removes the object from the index.
+     */
+    pub fn insert(&mut self, id: ObjectId, key: Option<IndexKey>) {
+        self.remove(id);
+
+        let key = match key {
+            Some(key) => key,
+            None => return
+        };
+
+        match self.kind {
+            IndexKind::Hash => self.hashed.entry(key.clone()).or_default().insert(id),
+            IndexKind::Ordered => self.ordered.entry(key.clone()).or_default().insert(id),
+            IndexKind::None => return
+        };
+
+        self.keys.insert(id, key);
+    }
+
+    pub fn remove(&mut self, id: ObjectId) {
+        let key = match self.keys.remove(&id) {
+            Some(key) => key,
+            None => return
+        };
+
+        let ids = match self.kind {
+            IndexKind::Hash => self.hashed.get_mut(&key),
+            IndexKind::Ordered => self.ordered.get_mut(&key),
+            IndexKind::None => None
+        };
+
+        if let Some(ids) = ids {
+            ids.remove(&id);
+
+            if ids.is_empty() {
+                self.hashed.remove(&key);
+                self.ordered.remove(&key);
+            }
+        }
+    }
+
+    pub fn key_of(&self, id: ObjectId) -> Option<&IndexKey> {
+        self.keys.get(&id)
+    }
+
+    /**
+     * Find the objects which may pass a filter comparing the property with
+     * a key. Returns None when the index cannot serve the comparison, the
+     * objects must then be scanned.
+     */
+    pub fn lookup(&self, comparison: Comparison, key: &IndexKey) -> Option<BTreeSet<ObjectId>> {
+        if comparison == Comparison::Equal {
+            let ids = match self.kind {
+                IndexKind::Hash => self.hashed.get(key),
+                IndexKind::Ordered => self.ordered.get(key),
+                IndexKind::None => return None
+            };
+
+            return Some(ids.cloned().unwrap_or_default());
+        }
+
+        if self.kind != IndexKind::Ordered {
+            return None;
+        }
+
+        // Keys of the same kind as the searched one, bounds are included
+        // since different numbers can share a key.
+        let (first, last) = match key {
+            IndexKey::Number(_) => (Bound::Unbounded, Bound::Excluded(IndexKey::Str(String::new()))),
+            IndexKey::Str(_) => (Bound::Included(IndexKey::Str(String::new())), Bound::Excluded(IndexKey::Other(0, vec![]))),
+            IndexKey::Other(_, _) => return None
+        };
+
+        let range = match comparison {
+            Comparison::Less | Comparison::LessOrEqual => (first, Bound::Included(key.clone())),
+            Comparison::Greater | Comparison::GreaterOrEqual => (Bound::Included(key.clone()), last),
+            _ => return None
+        };
+
+        Some(self.ordered.range(range).flat_map(|(_, ids)| ids.iter().cloned()).collect())
+    }
+}
+
+impl Default for PropertyIndex {
+    /**
+     * An empty hash index.
+     */
+    fn default() -> PropertyIndex {
+        PropertyIndex::new(IndexKind::Hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(value: PropertyValue) -> Option<IndexKey> {
+        IndexKey::from_value(&value)
+    }
+
+    #[test]
+    fn test_number_keys() {
+        let numbers = [f64::NEG_INFINITY, -1e300, -2.5, -0.0, 1e-300, 3.0, 1e300, f64::INFINITY];
+
+        for pair in numbers.windows(2) {
+            assert!(number_key(pair[0]) < number_key(pair[1]));
+        }
+
+        assert_eq!(number_key(0.0), number_key(-0.0));
+        assert_eq!(key(PropertyValue::Int(3)), key(PropertyValue::Float(3.0)));
+        assert_eq!(key(PropertyValue::Vec2(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_hash_index() {
+        let mut index = PropertyIndex::new(IndexKind::Hash);
+        index.insert(1, key(PropertyValue::Str(String::from("Pirates"))));
+        index.insert(2, key(PropertyValue::Str(String::from("Navy"))));
+        index.insert(3, key(PropertyValue::Str(String::from("Pirates"))));
+
+        let pirates = key(PropertyValue::Str(String::from("Pirates"))).unwrap();
+        assert_eq!(index.lookup(Comparison::Equal, &pirates), Some(vec![1, 3].into_iter().collect()));
+        assert_eq!(index.lookup(Comparison::Less, &pirates), None);
+
+        // Moving and removing objects keeps a single entry per object.
+        index.insert(1, key(PropertyValue::Str(String::from("Navy"))));
+        index.remove(3);
+        assert_eq!(index.lookup(Comparison::Equal, &pirates), Some(BTreeSet::new()));
+        assert!(index.hashed.values().all(|ids| !ids.is_empty()));
+    }
+
+    #[test]
+    fn test_ordered_index() {
+        let mut index = PropertyIndex::new(IndexKind::Ordered);
+        index.insert(1, key(PropertyValue::Int(50)));
+        index.insert(2, key(PropertyValue::Int(10)));
+        index.insert(3, key(PropertyValue::Float(15.5)));
+        index.insert(4, key(PropertyValue::Str(String::from("Full"))));
+
+        let twenty = key(PropertyValue::Int(20)).unwrap();
+        assert_eq!(index.lookup(Comparison::Less, &twenty), Some(vec![2, 3].into_iter().collect()));
+        assert_eq!(index.lookup(Comparison::GreaterOrEqual, &twenty), Some(vec![1].into_iter().collect()));
+
+        let text = key(PropertyValue::Str(String::from("A"))).unwrap();
+        assert_eq!(index.lookup(Comparison::Greater, &text), Some(vec![4].into_iter().collect()));
+        assert_eq!(index.lookup(Comparison::NotEqual, &text), None);
+    }
+}
@@ -13,6 +13,11 @@ use std::ops::Deref;
 use std::iter::*;
 
 mod sessions;
+mod games;
+mod objects;
+mod spatial;
+mod indexes;
+mod schemas;
 mod commands;
 mod testclients;
 mod utils;
@@ -20,15 +25,26 @@ mod utils;
 use utils::*;
 use commands::*;
 use sessions::*;
+use games::*;
+use objects::*;
+use spatial::*;
+use schemas::*;
+
+// Time between two runs of the thread deleting expired and orphaned objects.
+static REAPER_INTERVAL_MS: u64 = 500;
 
 // Hosts various server objects.
 struct ServerContext {
-    pub sessions: Mutex<SessionManager>
+    pub sessions: Mutex<SessionManager>,
+    pub games: Mutex<GameManager>,
+    pub objects: Mutex<ObjectStore>
 }
 
 fn create_server_context() -> ServerContext {
     ServerContext {
-        sessions: Mutex::new(SessionManager::new())
+        sessions: Mutex::new(SessionManager::new()),
+        games: Mutex::new(GameManager::new()),
+        objects: Mutex::new(ObjectStore::new())
     }
 }
 
@@ -48,6 +64,8 @@ fn start_server_thread() {
 
     let context = Arc::new(Mutex::new(create_server_context()));
 
+    start_reaper_thread(context.clone());
+
     // Server Accept thread
     std::thread::spawn(move || {
         let t = std::net::TcpListener::bind("127.0.0.1:5555").unwrap();
@@ -75,6 +93,96 @@ fn start_server_thread() {
     });
 }
 
+/**
+ * Start the thread enforcing the lifetime of objects. It deletes the objects
+ * whose ttl ran out and releases the objects of owners who are no longer
+ * connected, for example when their client crashed before its thread could
+ * clean up. It also starts the game sessions whose countdown ended.
+ */
+fn start_reaper_thread(context: Arc<Mutex<ServerContext>>) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(REAPER_INTERVAL_MS));
+
+            let ctx = context.lock().unwrap();
+            let now = chrono::UTC::now();
+
+            if let Err(error) = advance_phases(&ctx, now) {
+                log_error(&error);
+            }
+
+            if let Err(error) = reap_objects(&ctx, now) {
+                log_error(&error);
+            }
+        }
+    });
+}
+
+/**
+ * Move the game sessions whose countdown ended to the running phase and
+ * broadcast the change to their members.
+ */
+fn advance_phases(ctx: &ServerContext, now: chrono::DateTime<chrono::UTC>) -> Result<(), String> {
+    let started: Vec<(String, PhaseReply)> = {
+        let mut games = ctx.games.lock().unwrap();
+
+        games.update_phases(now).into_iter().filter_map(|name| {
+            let reply = games.get_game(&name).map(|g| PhaseReply::from_info(g.phase, g.start_timestamp_millis()));
+
+            reply.map(|reply| (name, reply))
+        }).collect()
+    };
+
+    for (game_name, reply) in started {
+        send_to_game(ctx, &game_name, &reply.serialize())?;
+    }
+
+    Ok(())
+}
+
+fn reap_objects(ctx: &ServerContext, now: chrono::DateTime<chrono::UTC>) -> Result<(), String> {
+    let game_names = ctx.objects.lock().unwrap().world_names();
+
+    for game_name in game_names {
+        if ctx.games.lock().unwrap().get_game(&game_name).is_none() {
+            continue;
+        }
+
+        let mut holders = {
+            let mut store = ctx.objects.lock().unwrap();
+            let world = store.world_mut(&game_name);
+
+            [world.owners(), world.lease_holders()].concat()
+        };
+        holders.sort();
+        holders.dedup();
+
+        let orphans: Vec<String> = {
+            let sessions = ctx.sessions.lock().unwrap();
+
+            holders.into_iter().filter(|o| sessions.get_session(o).is_none()).collect()
+        };
+
+        for owner in orphans {
+            release_owned_objects(ctx, &game_name, &owner)?;
+        }
+
+        let (sequence, changes) = {
+            let mut store = ctx.objects.lock().unwrap();
+            let world = store.world_mut(&game_name);
+
+            let before = world.sequence();
+            world.remove_expired(now);
+
+            (world.sequence(), world.change_set(before))
+        };
+
+        notify_subscribers(ctx, &game_name, "", sequence, &changes)?;
+    }
+
+    Ok(())
+}
+
 fn start_client_thread(session: Arc<Mutex<PlayerSession>>, ctx: Arc<Mutex<ServerContext>>) {
 
     std::thread::spawn(move || {
@@ -106,18 +214,20 @@ fn start_client_thread(session: Arc<Mutex<PlayerSession>>, ctx: Arc<Mutex<Server
             socket.try_clone().unwrap()
         };
 
+        start_client_writer_thread(&session);
+
         // A bufreader is created using the TcpStream copy
         let mut br = BufReader::new(socket);
 
         loop {
-            let mut readbuf = vec![];
-            br.read_until(b'|', &mut readbuf).unwrap();
+            let readbuf = match read_frame(&mut br) {
+                Ok(readbuf) => readbuf,
+                Err(_) => {
+                    println!("Client is done. Exiting thread.");
 
-            if readbuf.is_empty() {
-                println!("Client is done. Exiting thread.");
-
-                break;
-            }
+                    break;
+                }
+            };
 
             // Lock the structures as close as possible to their callsites
             // Lock, call handle_user_packet(...) on it, save session and
@@ -138,6 +248,50 @@ fn start_client_thread(session: Arc<Mutex<PlayerSession>>, ctx: Arc<Mutex<Server
             let session_list = &ctx.sessions;
             session_list.lock().unwrap().save_session(&session);
         }
+
+        // The client is gone, forget its session so nothing gets sent to it
+        // anymore. This also drops its subscriptions.
+        let mut session = session.lock().unwrap();
+        let ctx = ctx.lock().unwrap();
+
+        ctx.sessions.lock().unwrap().remove_session(&session);
+
+        // Its objects go to the host or are deleted, as chosen when they
+        // were added.
+        sync_game_membership(&mut session, &ctx);
+
+        if let (Some(player), Some(game_name)) = (&session.player_name, &session.game) {
+            if let Err(error) = release_owned_objects(&ctx, game_name, player) {
+                log_error(&error);
+            }
+        }
+    });
+}
+
+/**
+ * Start the thread writing the outgoing messages of a client. Replies and
+ * pushed messages are queued in the outbox of the session so the thread of
+ * another client pushing a message never waits on a slow socket.
+ */
+fn start_client_writer_thread(session: &Arc<Mutex<PlayerSession>>) {
+    let (outbox, inbox) = mpsc::channel::<QueuedMessage>();
+
+    let writer = {
+        let mut session = session.lock().unwrap();
+        session.outbox = Some(outbox);
+
+        // The writer keeps its own copy without the outbox, otherwise the
+        // channel would never close and the thread would never end.
+        let mut writer = session.clone();
+        writer.outbox = None;
+
+        writer
+    };
+
+    std::thread::spawn(move || {
+        if let Err(error) = drain_outbox(inbox, |data| writer.write_message(data)) {
+            log_error(&error);
+        }
     });
 }
 
@@ -158,28 +312,145 @@ fn handle_user_packet(data: &[u8], session: &mut PlayerSession, ctx: &ServerCont
 
     println!("Received type {:?}", message_type);
 
+    sync_game_membership(session, ctx);
+
     match message_type {
         ref x if x == HELLO_MSG_ID => {
-            let msg = HelloCommand::from_client_message(&data).unwrap();
+            let msg = HelloCommand::from_client_message(data)?;
 
             handle_hello_message(&msg, session, ctx)?;
         },
         ref x if x == BYE_MSG_ID => {
-            let msg = ByeCommand::from_client_message(&data).unwrap();
+            let msg = ByeCommand::from_client_message(data)?;
 
             handle_bye_message(&msg, session, ctx)?;
         },
         ref x if x == PUTOBJ_MSG_ID => {
-            let msg = PutObjCommand::from_client_message(&data).unwrap();
+            let msg = PutObjCommand::from_client_message(data)?;
 
             handle_put_obj_message(&msg, session, ctx)?;
         },
+        ref x if x == TXN_MSG_ID => {
+            let msg = TxnCommand::from_client_message(data)?;
+
+            handle_txn_message(&msg, session, ctx)?;
+        },
+        ref x if x == GETOBJ_MSG_ID => {
+            let msg = GetObjCommand::from_client_message(data)?;
+
+            handle_get_obj_message(&msg, session, ctx)?;
+        },
+        ref x if x == CHANGES_MSG_ID => {
+            let msg = ChangesCommand::from_client_message(data)?;
+
+            handle_changes_message(&msg, session, ctx)?;
+        },
+        ref x if x == SNAPSHOT_MSG_ID => {
+            let msg = SnapshotCommand::from_client_message(data)?;
+
+            handle_snapshot_message(&msg, session, ctx)?;
+        },
+        ref x if x == REWIND_MSG_ID => {
+            let msg = RewindCommand::from_client_message(data)?;
+
+            handle_rewind_message(&msg, session, ctx)?;
+        },
+        ref x if x == LEASE_MSG_ID => {
+            let msg = LeaseCommand::from_client_message(data)?;
+
+            handle_lease_message(&msg, session, ctx)?;
+        },
+        ref x if x == QUERY_MSG_ID => {
+            let msg = QueryCommand::from_client_message(data)?;
+
+            handle_query_message(&msg, session, ctx)?;
+        },
+        ref x if x == TEMPLATE_MSG_ID => {
+            let msg = TemplateCommand::from_client_message(data)?;
+
+            handle_template_message(&msg, session, ctx)?;
+        },
+        ref x if x == TMPLIST_MSG_ID => {
+            let msg = TemplateListCommand::from_client_message(data)?;
+
+            handle_tmplist_message(&msg, session, ctx)?;
+        },
+        ref x if x == SPAWN_MSG_ID => {
+            let msg = SpawnCommand::from_client_message(data)?;
+
+            handle_spawn_message(&msg, session, ctx)?;
+        },
+        ref x if x == SUB_MSG_ID => {
+            let msg = SubCommand::from_client_message(data)?;
+
+            handle_sub_message(&msg, session, ctx)?;
+        },
+        ref x if x == INTEREST_MSG_ID => {
+            let msg = InterestCommand::from_client_message(data)?;
+
+            handle_interest_message(&msg, session, ctx)?;
+        },
+        ref x if x == CREATE_MSG_ID => {
+            let msg = CreateCommand::from_client_message(data)?;
+
+            handle_create_message(&msg, session, ctx)?;
+        },
+        ref x if x == JOIN_MSG_ID => {
+            let msg = JoinCommand::from_client_message(data)?;
+
+            handle_join_message(&msg, session, ctx)?;
+        },
+        ref x if x == GRANT_MSG_ID => {
+            let msg = GrantCommand::from_client_message(data)?;
+
+            handle_grant_message(&msg, session, ctx)?;
+        },
+        ref x if x == AUTH_MSG_ID => {
+            let msg = AuthorityCommand::from_client_message(data)?;
+
+            handle_authority_message(&msg, session, ctx)?;
+        },
+        ref x if x == CLASS_MSG_ID => {
+            let msg = ClassCommand::from_client_message(data)?;
+
+            handle_class_message(&msg, session, ctx)?;
+        },
+        ref x if x == INVITE_MSG_ID => {
+            let msg = InviteCommand::from_client_message(data)?;
+
+            handle_invite_message(&msg, session, ctx)?;
+        },
+        ref x if x == READY_MSG_ID => {
+            let msg = ReadyCommand::from_client_message(data)?;
+
+            handle_ready_message(&msg, session, ctx)?;
+        },
+        ref x if x == PHASE_MSG_ID => {
+            let msg = PhaseCommand::from_client_message(data)?;
+
+            handle_phase_message(&msg, session, ctx)?;
+        },
+        ref x if x == KICK_MSG_ID || x == BAN_MSG_ID => {
+            let msg = KickCommand::from_client_message(data)?;
+
+            handle_kick_message(&msg, session, ctx)?;
+        },
+        ref x if x == TEAM_MSG_ID => {
+            let msg = TeamCommand::from_client_message(data)?;
+
+            handle_team_message(&msg, session, ctx)?;
+        },
+        ref x if x == GAMEINFO_MSG_ID => {
+            let msg = GameInfoCommand::from_client_message(data)?;
+
+            handle_game_info_message(&msg, session, ctx)?;
+        },
         _ => {
             return Err("Unrecognized message type".to_string());
         }
     }
 
-    return Ok(());
+    Ok(())
 }
 
 /**
@@ -189,7 +460,7 @@ fn handle_user_packet(data: &[u8], session: &mut PlayerSession, ctx: &ServerCont
  * trailing null bytes trimmed.
  */
 fn find_message_type(data: &[u8]) -> Option<String> {
-    let header = &data[0..8];
+    let header = data.get(0..8)?;
 
     String::from_utf8(trim_vec_end(header)).ok()
 }
@@ -202,15 +473,22 @@ fn handle_hello_message(message: &HelloCommand, session: &mut PlayerSession, ctx
 
     match session.state {
         SessionState::Closed => {
-            session.state = SessionState::Active;
-
             let mut manager = ctx.sessions.lock().unwrap();
+
+            // Players are only known by their name, a second connection
+            // using it would act on behalf of the first one.
+            if manager.get_session(&message.user).is_some() {
+                return Err("Player name is already in use");
+            }
+
+            session.state = SessionState::Active;
+            session.set_username(message.user.clone());
             manager.add_session(session.clone());
 
-            return Ok(());
+            Ok(())
         },
         SessionState::Active => {
-            return Err("Session already open");
+            Err("Session already open")
         }
     }
 }
@@ -220,18 +498,1059 @@ fn handle_bye_message(message: &ByeCommand, session: &mut PlayerSession, _ctx: &
 
     match session.state {
         SessionState::Closed => {
-            return Err("Session is not open");
+            Err("Session is not open")
         },
         SessionState::Active => {
             session.state = SessionState::Active;
 
-            return Ok(());
+            Ok(())
         }
     }
 }
 
-fn handle_put_obj_message(message: &PutObjCommand, _session: &mut PlayerSession, _ctx: &ServerContext) -> Result<(), String> {
+fn handle_put_obj_message(message: &PutObjCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
     println!("Received PUTOBJ message {:?}", message);
 
-    Err("Not implemented".to_string())
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let (is_player, can_write, viewer) = {
+        let games = ctx.games.lock().unwrap();
+        let game = games.get_game(&game_name).ok_or("Game session does not exist")?;
+
+        (game.has_player(&player), game.can_write(&player, message.object_id), game.viewer(&player))
+    };
+
+    let result = {
+        let mut store = ctx.objects.lock().unwrap();
+        let world = store.world_mut(&game_name);
+
+        if is_put_allowed(world, message, &player, is_player, can_write) {
+            let before = world.sequence();
+            let now = chrono::UTC::now();
+
+            let reply = if message.operation.is_atomic() {
+                // Players granted write access to an object may still not be
+                // allowed to see the current value of the property.
+                world.apply_atomic(message, now).map(|r| {
+                    let value = r.value.filter(|v| world.get(message.object_id).is_some_and(|o| world.can_see(o, &v.name, &viewer)));
+
+                    OpResultReply::from_info(message.object_id, r.version, r.applied, value).serialize()
+                })
+            } else {
+                world.apply(message, &player, now)
+                     .map(|(id, version)| PutObjReply::from_info(id, version).serialize())
+            };
+
+            reply.map(|reply| (reply, world.sequence(), world.change_set(before)))
+                 .map_err(|e| (store_error_status(&e), e.to_string()))
+        } else {
+            Err((StatusCode::Denied, format!("Player {} is not allowed to write object {}", player, message.object_id)))
+        }
+    };
+
+    match result {
+        Ok((reply, sequence, changes)) => {
+            session.send_message(&reply)?;
+
+            notify_subscribers(ctx, &game_name, &player, sequence, &changes)
+        },
+        Err((code, error)) => {
+            send_status(session, PUTOBJ_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_lease_message(message: &LeaseCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received LEASE message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let is_player = ctx.games.lock().unwrap().get_game(&game_name).is_some_and(|game| game.has_player(&player));
+
+    let result = if !is_player {
+        Err((StatusCode::Denied, format!("Player {} cannot add objects to {}", player, game_name)))
+    } else if message.count == 0 || message.count > MAX_LEASE_SIZE {
+        Err((StatusCode::Invalid, format!("Leases hold between 1 and {} ids", MAX_LEASE_SIZE)))
+    } else {
+        ctx.objects.lock().unwrap()
+           .world_mut(&game_name)
+           .lease_ids(&player, message.count)
+           .map_err(|e| (store_error_status(&e), e.to_string()))
+    };
+
+    match result {
+        Ok(start) => session.send_message(&LeaseReply::from_info(start, message.count).serialize()),
+        Err((code, error)) => {
+            send_status(session, LEASE_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_spawn_message(message: &SpawnCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received SPAWN message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let is_player = ctx.games.lock().unwrap().get_game(&game_name).is_some_and(|game| game.has_player(&player));
+
+    let result = if !is_player {
+        Err((StatusCode::Denied, format!("Player {} cannot add objects to {}", player, game_name)))
+    } else {
+        let mut store = ctx.objects.lock().unwrap();
+        let world = store.world_mut(&game_name);
+        let before = world.sequence();
+
+        world.spawn(message, &player, chrono::UTC::now())
+             .map(|(id, version)| (PutObjReply::for_request(SPAWN_MSG_ID, id, version), world.sequence(), world.change_set(before)))
+             .map_err(|e| (store_error_status(&e), e.to_string()))
+    };
+
+    match result {
+        Ok((reply, sequence, changes)) => {
+            session.send_message(&reply.serialize())?;
+
+            notify_subscribers(ctx, &game_name, &player, sequence, &changes)
+        },
+        Err((code, error)) => {
+            send_status(session, SPAWN_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_txn_message(message: &TxnCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received TXN message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let (is_player, can_write) = {
+        let games = ctx.games.lock().unwrap();
+        let game = games.get_game(&game_name).ok_or("Game session does not exist")?;
+
+        let can_write: Vec<bool> = message.operations.iter().map(|o| game.can_write(&player, o.object_id)).collect();
+
+        (game.has_player(&player), can_write)
+    };
+
+    let result = {
+        let mut store = ctx.objects.lock().unwrap();
+        let world = store.world_mut(&game_name);
+
+        // Objects added earlier in the transaction do not exist yet, they
+        // are allowed since the player will own them.
+        let denied = message.operations.iter()
+                                       .zip(can_write)
+                                       .position(|(o, can_write)| !is_put_allowed(world, o, &player, is_player, can_write));
+
+        match denied {
+            Some(index) => {
+                let object_id = message.operations[index].object_id;
+
+                Err((StatusCode::Denied, format!("Operation {} : Player {} is not allowed to write object {}", index, player, object_id)))
+            },
+            None => {
+                let before = world.sequence();
+
+                world.apply_all(&message.operations, &player, chrono::UTC::now())
+                     .map(|versions| (versions, world.sequence(), world.change_set(before)))
+                     .map_err(|(index, e)| (store_error_status(&e), format!("Operation {} : {}", index, e)))
+            }
+        }
+    };
+
+    match result {
+        Ok((versions, sequence, changes)) => {
+            session.send_message(&TxnReply::from_info(sequence, versions).serialize())?;
+
+            notify_subscribers(ctx, &game_name, &player, sequence, &changes)
+        },
+        Err((code, error)) => {
+            send_status(session, TXN_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_get_obj_message(message: &GetObjCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received GETOBJ message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, GETOBJ_MSG_ID)?;
+
+    let entries = {
+        let store = ctx.objects.lock().unwrap();
+        let world = store.world(&game_name);
+
+        message.object_names.iter().flat_map(|name| {
+            let (world, id) = match world.and_then(|w| w.find_by_name(name).map(|o| (w, o.id))) {
+                Some(found) => found,
+                None => return vec![ObjectEntry::not_found(name)]
+            };
+
+            let mut ids = vec![id];
+            if message.include_children {
+                ids.append(&mut world.descendants(id));
+            }
+
+            ids.iter().map(|id| {
+                let object = world.get(*id).unwrap();
+                let entry = object.to_entry(&world.resolve_property_names(object, &message.property_names));
+
+                world.visible_entry(object, entry, &viewer)
+            }).collect()
+        }).collect()
+    };
+
+    send_read_reply(session, &viewer, &GetObjReply::from_info(entries).serialize())
+}
+
+fn handle_changes_message(message: &ChangesCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received CHANGES message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, CHANGES_MSG_ID)?;
+
+    let reply = {
+        let store = ctx.objects.lock().unwrap();
+
+        match store.world(&game_name) {
+            Some(world) => {
+                let (entries, reset) = world.changes_since(message.since, session.interest.as_ref(), &viewer);
+
+                ChangesReply::from_info(world.sequence(), reset, entries)
+            },
+            None => ChangesReply::from_info(0, false, vec![])
+        }
+    };
+
+    send_read_reply(session, &viewer, &reply.serialize())
+}
+
+fn handle_snapshot_message(message: &SnapshotCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received SNAPSHOT message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, SNAPSHOT_MSG_ID)?;
+
+    // The objects are copied under the store lock so the snapshot matches
+    // its sequence number, the chunks are sent once the lock is released.
+    let (sequence, entries) = {
+        let store = ctx.objects.lock().unwrap();
+
+        match store.world(&game_name) {
+            Some(world) => (world.sequence(), world.snapshot(session.interest.as_ref(), &viewer)),
+            None => (0, vec![])
+        }
+    };
+
+    for chunk in SnapshotChunk::split(sequence, entries, message.effective_chunk_size()) {
+        send_read_reply(session, &viewer, &chunk.serialize())?;
+    }
+
+    Ok(())
+}
+
+fn handle_rewind_message(message: &RewindCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received REWIND message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, REWIND_MSG_ID)?;
+
+    let secs = (message.timestamp / 1000) as i64;
+    let nanos = ((message.timestamp % 1000) * 1_000_000) as u32;
+
+    let at = match chrono::TimeZone::timestamp_opt(&chrono::UTC, secs, nanos).single() {
+        Some(at) => at,
+        None => {
+            let error = format!("Invalid timestamp {}", message.timestamp);
+            send_status(session, REWIND_MSG_ID, StatusCode::Invalid, &error)?;
+
+            return Err(error);
+        }
+    };
+
+    let result = {
+        let store = ctx.objects.lock().unwrap();
+
+        match store.world(&game_name) {
+            Some(world) => world.state_at(message.object_id, at, message.mode, &message.property_names, &viewer),
+            None => Err(StoreError::NotFound(message.object_id))
+        }
+    };
+
+    match result {
+        Ok(entry) => send_read_reply(session, &viewer, &GetObjReply::for_request(REWIND_MSG_ID, vec![entry]).serialize()),
+        Err(error) => {
+            send_status(session, REWIND_MSG_ID, store_error_status(&error), &error.to_string())?;
+
+            Err(error.to_string())
+        }
+    }
+}
+
+fn handle_query_message(message: &QueryCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received QUERY message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, QUERY_MSG_ID)?;
+
+    let entries = {
+        let store = ctx.objects.lock().unwrap();
+
+        store.world(&game_name).map_or(vec![], |w| w.query(message, &viewer))
+    };
+
+    send_read_reply(session, &viewer, &GetObjReply::for_request(QUERY_MSG_ID, entries).serialize())
+}
+
+fn handle_sub_message(message: &SubCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received SUB message {:?}", message);
+
+    check_read_access(session, ctx, SUB_MSG_ID)?;
+
+    let subscription = Subscription {
+        pattern: message.pattern.clone(),
+        prefix: message.prefix
+    };
+
+    match message.operation {
+        SubOperation::Subscribe => session.subscribe(subscription),
+        SubOperation::Unsubscribe => session.unsubscribe(&subscription)
+    }
+
+    send_status(session, SUB_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_interest_message(message: &InterestCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received INTEREST message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    let viewer = check_read_access(session, ctx, INTEREST_MSG_ID)?;
+
+    let area = Area {
+        min_x: message.min_x,
+        min_y: message.min_y,
+        max_x: message.max_x,
+        max_y: message.max_y
+    };
+
+    if message.enabled && !area.is_valid() {
+        let error = String::from("Area of interest is not finite or has its minimum above its maximum");
+        send_status(session, INTEREST_MSG_ID, StatusCode::Invalid, &error)?;
+
+        return Err(error);
+    }
+
+    let previous = session.interest;
+    session.interest = if message.enabled { Some(area) } else { None };
+
+    // Tell the client about the objects entering and leaving its view, a
+    // client without area sees every object. When the area is removed the
+    // client can fetch everything with CHANGES.
+    let mut entries = vec![];
+
+    if let Some(current) = session.interest {
+        let store = ctx.objects.lock().unwrap();
+
+        if let Some(world) = store.world(&game_name) {
+            let now_inside = world.objects_in(&current, &viewer);
+            let was_inside = match previous {
+                Some(previous) => world.objects_in(&previous, &viewer),
+                None => world.positioned_objects(&viewer)
+            };
+
+            for object in &now_inside {
+                if !was_inside.contains(object) {
+                    entries.push(world.visible_entry(object, ObjectEntry { status: EntryStatus::Entered, ..object.to_entry(&[]) }, &viewer));
+                }
+            }
+
+            for object in was_inside {
+                if !now_inside.contains(&object) {
+                    entries.push(ObjectEntry { status: EntryStatus::Left, properties: vec![], ..object.to_entry(&[]) });
+                }
+            }
+        }
+    }
+
+    send_read_reply(session, &viewer, &GetObjReply::for_request(INTEREST_MSG_ID, entries).serialize())
+}
+
+fn handle_create_message(message: &CreateCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received CREATE message {:?}", message);
+
+    let player = get_player_name(session)?;
+
+    let result = match session.game {
+        Some(_) => Err(String::from("Player is already in a game session")),
+        None if message.spectator_delay > MAX_SPECTATOR_DELAY => {
+            Err(format!("Spectator delay cannot be above {} seconds", MAX_SPECTATOR_DELAY))
+        },
+        None if message.history_ms > MAX_HISTORY_MS => {
+            Err(format!("History cannot be above {} milliseconds", MAX_HISTORY_MS))
+        },
+        None => {
+            let mut games = ctx.games.lock().unwrap();
+
+            games.create_game(&message.name, &message.password, &player).map(|game| {
+                game.max_players = message.max_players;
+                game.spectator_delay = message.spectator_delay;
+            })?;
+
+            ctx.objects.lock().unwrap().world_mut(&message.name).set_history_duration(message.history_ms);
+
+            Ok(())
+        }
+    };
+
+    if let Err(error) = result {
+        send_status(session, CREATE_MSG_ID, StatusCode::Invalid, &error)?;
+
+        return Err(error);
+    }
+
+    session.game = Some(message.name.clone());
+
+    send_status(session, CREATE_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_join_message(message: &JoinCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received JOIN message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let address = session.peer_address();
+
+    let result = match session.game {
+        Some(_) => Err(String::from("Player is already in a game session")),
+        None if !message.invite.is_empty() => {
+            let mut games = ctx.games.lock().unwrap();
+
+            games.join_game_with_invite(&message.invite, &player, address, message.mode, chrono::UTC::now())
+        },
+        None => {
+            let mut games = ctx.games.lock().unwrap();
+
+            games.join_game(&message.name, &message.password, &player, address, message.mode).map(|_| message.name.clone())
+        }
+    };
+
+    let game_name = match result {
+        Ok(name) => name,
+        Err(error) => {
+            send_status(session, JOIN_MSG_ID, StatusCode::Denied, &error)?;
+
+            return Err(error);
+        }
+    };
+
+    session.game = Some(game_name);
+
+    send_status(session, JOIN_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_grant_message(message: &GrantCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received GRANT message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        let game = games.get_game_mut(&game_name).ok_or("Game session does not exist")?;
+
+        if game.is_host(&player) {
+            game.grant_write(&message.player, message.object_id).map_err(|e| (StatusCode::NotFound, e))
+        } else {
+            Err((StatusCode::Denied, String::from("Only the host can grant write rights")))
+        }
+    };
+
+    if let Err((code, error)) = result {
+        send_status(session, GRANT_MSG_ID, code, &error)?;
+
+        return Err(error);
+    }
+
+    send_status(session, GRANT_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_authority_message(message: &AuthorityCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received AUTH message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let (host, target_is_player) = {
+        let games = ctx.games.lock().unwrap();
+        let game = games.get_game(&game_name).ok_or("Game session does not exist")?;
+
+        (game.host.clone(), game.has_player(&message.player))
+    };
+
+    let owner = {
+        let store = ctx.objects.lock().unwrap();
+
+        store.world(&game_name).and_then(|w| w.get(message.object_id)).map(|o| o.owner.clone())
+    };
+
+    let result = match (owner, message.operation) {
+        (None, _) => Err((StatusCode::NotFound, format!("Object {} does not exist", message.object_id))),
+        (Some(owner), AuthorityOperation::Request) => {
+            // The host decides when the owner is not connected.
+            let sessions = ctx.sessions.lock().unwrap();
+            let request = AuthorityCommand::request(message.object_id, &player);
+
+            match sessions.get_session(&owner).or_else(|| sessions.get_session(&host)) {
+                Some(target) => target.send_message(&request.serialize()).map_err(|e| (StatusCode::NotFound, e)),
+                None => Err((StatusCode::NotFound, String::from("Neither the owner nor the host is connected")))
+            }
+        },
+        (Some(owner), AuthorityOperation::Grant) => {
+            if owner != player && host != player {
+                Err((StatusCode::Denied, String::from("Only the owner or the host can give an object")))
+            } else if !target_is_player {
+                Err((StatusCode::Invalid, format!("{} is not a player of the game session", message.player)))
+            } else {
+                let mut store = ctx.objects.lock().unwrap();
+
+                store.world_mut(&game_name)
+                     .set_owner(message.object_id, &message.player)
+                     .map_err(|e| (store_error_status(&e), e.to_string()))
+            }
+        }
+    };
+
+    if let Err((code, error)) = result {
+        send_status(session, AUTH_MSG_ID, code, &error)?;
+
+        return Err(error);
+    }
+
+    if message.operation == AuthorityOperation::Grant {
+        send_to_game(ctx, &game_name, &OwnerMessage::from_info(message.object_id, &message.player).serialize())?;
+    }
+
+    send_status(session, AUTH_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_class_message(message: &ClassCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received CLASS message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = if ctx.games.lock().unwrap().is_host_of(&game_name, &player) {
+        ClassSchema::from_command(message).map_err(|e| (StatusCode::Invalid, e))
+    } else {
+        Err((StatusCode::Denied, String::from("Only the host can register object classes")))
+    };
+
+    match result {
+        Ok(schema) => {
+            ctx.objects.lock().unwrap().world_mut(&game_name).register_class(schema);
+
+            send_status(session, CLASS_MSG_ID, StatusCode::Ok, "")
+        },
+        Err((code, error)) => {
+            send_status(session, CLASS_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_template_message(message: &TemplateCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received TEMPLATE message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = if ctx.games.lock().unwrap().is_host_of(&game_name, &player) {
+        ctx.objects.lock().unwrap()
+           .world_mut(&game_name)
+           .register_template(&message.name, &message.class_name, &message.properties)
+           .map(|template| template.to_entry())
+           .map_err(|e| (store_error_status(&e), e.to_string()))
+    } else {
+        Err((StatusCode::Denied, String::from("Only the host can register object templates")))
+    };
+
+    match result {
+        Ok(entry) => {
+            send_to_game(ctx, &game_name, &TemplateListReply::for_request(TEMPLATE_MSG_ID, vec![entry]).serialize())?;
+
+            send_status(session, TEMPLATE_MSG_ID, StatusCode::Ok, "")
+        },
+        Err((code, error)) => {
+            send_status(session, TEMPLATE_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_tmplist_message(message: &TemplateListCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received TMPLIST message {:?}", message);
+
+    let game_name = get_game_name(session)?;
+
+    check_read_access(session, ctx, TMPLIST_MSG_ID)?;
+
+    let templates = {
+        let store = ctx.objects.lock().unwrap();
+
+        store.world(&game_name).map_or(vec![], |w| w.templates().iter().map(|t| t.to_entry()).collect())
+    };
+
+    session.send_message(&TemplateListReply::for_request(TMPLIST_MSG_ID, templates).serialize())
+}
+
+fn handle_invite_message(message: &InviteCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received INVITE message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        if !games.is_host_of(&game_name, &player) {
+            Err((StatusCode::Denied, String::from("Only the host can manage invite codes")))
+        } else {
+            match message.operation {
+                InviteOperation::Create if message.lifetime > MAX_INVITE_LIFETIME => {
+                    Err((StatusCode::Invalid, format!("Invite codes last at most {} seconds", MAX_INVITE_LIFETIME)))
+                },
+                InviteOperation::Create => {
+                    let lifetime = chrono::Duration::seconds(message.lifetime as i64);
+
+                    games.create_invite(&game_name, lifetime, message.max_uses, chrono::UTC::now())
+                         .map(|invite| Some(InviteReply::from_info(&invite.code, invite.expires_at.timestamp() as u64)))
+                         .map_err(|e| (StatusCode::Invalid, e))
+                },
+                InviteOperation::Revoke => {
+                    games.revoke_invite(&game_name, &message.code)
+                         .map(|_| None)
+                         .map_err(|e| (StatusCode::NotFound, e))
+                }
+            }
+        }
+    };
+
+    match result {
+        Ok(Some(reply)) => session.send_message(&reply.serialize()),
+        Ok(None) => send_status(session, INVITE_MSG_ID, StatusCode::Ok, ""),
+        Err((code, error)) => {
+            send_status(session, INVITE_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_ready_message(message: &ReadyCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received READY message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        let game = games.get_game_mut(&game_name).ok_or("Game session does not exist")?;
+
+        game.set_ready(&player, message.ready)
+    };
+
+    if let Err(error) = result {
+        send_status(session, READY_MSG_ID, StatusCode::Invalid, &error)?;
+
+        return Err(error);
+    }
+
+    send_status(session, READY_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_phase_message(message: &PhaseCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received PHASE message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        let game = games.get_game_mut(&game_name).ok_or("Game session does not exist")?;
+
+        if !game.is_host(&player) {
+            Err((StatusCode::Denied, String::from("Only the host can change the game phase")))
+        } else if message.phase == GamePhase::Countdown && message.countdown > MAX_COUNTDOWN {
+            Err((StatusCode::Invalid, format!("Countdowns last at most {} seconds", MAX_COUNTDOWN)))
+        } else {
+            // The countdown is ignored by the other phases.
+            let countdown = chrono::Duration::seconds(message.countdown.min(MAX_COUNTDOWN) as i64);
+
+            game.transition(message.phase, countdown, chrono::UTC::now())
+                .map(|_| PhaseReply::from_info(game.phase, game.start_timestamp_millis()))
+                .map_err(|e| (StatusCode::Invalid, e))
+        }
+    };
+
+    match result {
+        Ok(reply) => send_to_game(ctx, &game_name, &reply.serialize()),
+        Err((code, error)) => {
+            send_status(session, PHASE_MSG_ID, code, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+fn handle_kick_message(message: &KickCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received {} message {:?}", message.id, message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let target = ctx.sessions.lock().unwrap().get_session(&message.player);
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        let game = games.get_game_mut(&game_name).ok_or("Game session does not exist")?;
+
+        if !game.is_host(&player) {
+            Err((StatusCode::Denied, String::from("Only the host can remove players")))
+        } else {
+            let removed = game.remove_member(&message.player);
+
+            // A ban also keeps out players who already left the game session.
+            if message.is_ban() && !game.is_host(&message.player) {
+                game.ban(&message.player, target.as_ref().and_then(|t| t.peer_address()));
+
+                Ok(removed.is_ok())
+            } else {
+                removed.map(|_| true).map_err(|e| (StatusCode::Invalid, e))
+            }
+        }
+    };
+
+    let was_member = match result {
+        Ok(was_member) => was_member,
+        Err((code, error)) => {
+            send_status(session, &message.id, code, &error)?;
+
+            return Err(error);
+        }
+    };
+
+    if !was_member {
+        return send_status(session, &message.id, StatusCode::Ok, "");
+    }
+
+    if let Err(error) = release_owned_objects(ctx, &game_name, &message.player) {
+        log_error(&error);
+    }
+
+    // Tell the removed client why it was removed. Its own thread notices it
+    // left the game session on its next message.
+    if let Some(target) = target {
+        if let Err(error) = target.send_message(&message.serialize()) {
+            log_error(&error);
+        }
+
+        if message.close {
+            target.close();
+        }
+    }
+
+    send_status(session, &message.id, StatusCode::Ok, "")
+}
+
+fn handle_team_message(message: &TeamCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received TEAM message {:?}", message);
+
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let result = {
+        let mut games = ctx.games.lock().unwrap();
+        let game = games.get_game_mut(&game_name).ok_or("Game session does not exist")?;
+
+        if !game.is_host(&player) {
+            Err((StatusCode::Denied, String::from("Only the host can assign teams")))
+        } else {
+            game.set_team(&message.player, &message.team).map_err(|e| (StatusCode::Invalid, e))
+        }
+    };
+
+    if let Err((code, error)) = result {
+        send_status(session, TEAM_MSG_ID, code, &error)?;
+
+        return Err(error);
+    }
+
+    send_status(session, TEAM_MSG_ID, StatusCode::Ok, "")
+}
+
+fn handle_game_info_message(message: &GameInfoCommand, session: &mut PlayerSession, ctx: &ServerContext) -> Result<(), String> {
+    println!("Received GAMEINFO message {:?}", message);
+
+    let reply = {
+        let games = ctx.games.lock().unwrap();
+
+        games.get_game(&message.name).map(|game| GameInfoReply {
+            id: String::from(GAMEINFO_MSG_ID),
+            name: game.name.clone(),
+            host: game.host.clone(),
+            max_players: game.max_players,
+            spectator_delay: game.spectator_delay,
+            phase: game.phase,
+            start_at: game.start_timestamp_millis(),
+            players: game.players.clone(),
+            spectators: game.spectators.clone()
+        })
+    };
+
+    match reply {
+        Some(reply) => session.send_message(&reply.serialize()),
+        None => {
+            let error = format!("Game session {} does not exist", message.name);
+            send_status(session, GAMEINFO_MSG_ID, StatusCode::NotFound, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+/**
+ * Forget the game session of a player if it is no longer part of it, which
+ * happens when the host removed the player from the session.
+ */
+fn sync_game_membership(session: &mut PlayerSession, ctx: &ServerContext) {
+    let (player, game_name) = match (&session.player_name, &session.game) {
+        (Some(player), Some(game_name)) => (player.clone(), game_name.clone()),
+        _ => return
+    };
+
+    let games = ctx.games.lock().unwrap();
+    let is_member = games.get_game(&game_name).is_some_and(|game| game.has_member(&player));
+
+    if !is_member {
+        session.game = None;
+    }
+}
+
+/**
+ * Send a STATUS reply to the client about one of its requests.
+ */
+fn send_status(session: &PlayerSession, request: &str, code: StatusCode, msg: &str) -> Result<(), String> {
+    let reply = StatusCommand::from_info(request, code, msg);
+
+    session.send_message(&reply.serialize())
+}
+
+/**
+ * Send a message to every player and spectator of a game session.
+ */
+fn send_to_game(ctx: &ServerContext, game_name: &str, data: &[u8]) -> Result<(), String> {
+    let members: Vec<String> = {
+        let games = ctx.games.lock().unwrap();
+        let game = games.get_game(game_name).ok_or("Game session does not exist")?;
+
+        game.players.iter().chain(game.spectators.iter()).cloned().collect()
+    };
+
+    let sessions = ctx.sessions.lock().unwrap();
+
+    for member in members {
+        if let Some(member_session) = sessions.get_session(&member) {
+            if let Err(error) = member_session.send_message(data) {
+                log_error(&error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Checks if a player can apply a PUTOBJ. Any player can add objects and owns
+ * them. Existing objects can be written by their owner, the host and the
+ * players granted write rights.
+ */
+fn is_put_allowed(world: &World, message: &PutObjCommand, player: &str, is_player: bool, can_write: bool) -> bool {
+    match message.operation {
+        PutOperation::Add => is_player,
+        _ => can_write || world.get(message.object_id).is_none_or(|o| o.owner == player)
+    }
+}
+
+/**
+ * Apply the owner leave policy of the objects of a player who left a game
+ * session, and release the ids it leased. Objects given back to the host are
+ * announced to the session and deleted objects are pushed to the subscribers.
+ */
+fn release_owned_objects(ctx: &ServerContext, game_name: &str, player: &str) -> Result<(), String> {
+    let host = {
+        let games = ctx.games.lock().unwrap();
+
+        games.get_game(game_name).map(|g| g.host.clone()).ok_or("Game session does not exist")?
+    };
+
+    let (reverted, sequence, changes) = {
+        let mut store = ctx.objects.lock().unwrap();
+        let world = store.world_mut(game_name);
+
+        world.release_leases(player);
+
+        let before = world.sequence();
+        let owned: Vec<(ObjectId, OwnerLeavePolicy)> = world.owned_by(player).iter().map(|o| (o.id, o.on_owner_leave)).collect();
+
+        let mut reverted = vec![];
+        for (id, policy) in owned {
+            match policy {
+                // The object may be gone already with a deleted parent.
+                OwnerLeavePolicy::Delete if world.get(id).is_some() => {
+                    world.delete(id, 0).map_err(|e| e.to_string())?;
+                },
+                OwnerLeavePolicy::Delete => {},
+                OwnerLeavePolicy::RevertToHost if player != host => {
+                    world.set_owner(id, &host).map_err(|e| e.to_string())?;
+                    reverted.push(id);
+                },
+                OwnerLeavePolicy::RevertToHost => {}
+            }
+        }
+
+        (reverted, world.sequence(), world.change_set(before))
+    };
+
+    for id in reverted {
+        send_to_game(ctx, game_name, &OwnerMessage::from_info(id, &host).serialize())?;
+    }
+
+    notify_subscribers(ctx, game_name, player, sequence, &changes)
+}
+
+/**
+ * Checks that the client is a player or a spectator of its game session
+ * before letting it read objects. A Denied status is sent otherwise.
+ *
+ * Returns what the client is allowed to see of the objects.
+ */
+fn check_read_access(session: &PlayerSession, ctx: &ServerContext, request: &str) -> Result<Viewer, String> {
+    let player = get_player_name(session)?;
+    let game_name = get_game_name(session)?;
+
+    let viewer = ctx.games.lock().unwrap().get_game(&game_name).filter(|game| game.has_member(&player)).map(|game| game.viewer(&player));
+
+    match viewer {
+        Some(viewer) => Ok(viewer),
+        None => {
+            let error = format!("Player {} is not allowed to read objects of {}", player, game_name);
+            send_status(session, request, StatusCode::Denied, &error)?;
+
+            Err(error)
+        }
+    }
+}
+
+/**
+ * Send the reply to an object read. Spectators receive it late, like the
+ * changes pushed to them, so they only ever see the world as it was
+ * spectator_delay seconds ago.
+ */
+fn send_read_reply(session: &PlayerSession, viewer: &Viewer, data: &[u8]) -> Result<(), String> {
+    session.send_message_delayed(data, std::time::Duration::from_secs(viewer.delay))
+}
+
+/**
+ * Status code reported to clients for an object store error.
+ */
+fn store_error_status(error: &StoreError) -> StatusCode {
+    match error {
+        StoreError::AlreadyExists(_) | StoreError::NameTaken(_) => StatusCode::Conflict,
+        StoreError::VersionConflict { .. } | StoreError::NotApplied { .. } => StatusCode::Conflict,
+        StoreError::IdLeased { .. } | StoreError::IdsExhausted => StatusCode::Conflict,
+        StoreError::TooManyLeases(_) => StatusCode::Conflict,
+        StoreError::StaleTemplate { .. } => StatusCode::Conflict,
+        StoreError::NotFound(_) | StoreError::NoHistory(_) => StatusCode::NotFound,
+        StoreError::TemplateNotFound(_) => StatusCode::NotFound,
+        StoreError::InvalidParent { .. } | StoreError::IdReserved(_) => StatusCode::Invalid,
+        StoreError::InvalidValue(_) | StoreError::SchemaViolation(_) => StatusCode::Invalid
+    }
+}
+
+/**
+ * Push object changes to the members of a game session subscribed to them,
+ * except the client that made the change. Spectators receive the changes
+ * after the spectator delay of the session. Clients with an area of interest
+ * only receive the objects inside it, and every client only receives the
+ * properties it is allowed to see.
+ */
+fn notify_subscribers(ctx: &ServerContext, game_name: &str, sender: &str, sequence: u64, changes: &[ObjectChange]) -> Result<(), String> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let members: Vec<Viewer> = {
+        let games = ctx.games.lock().unwrap();
+        let game = games.get_game(game_name).ok_or("Game session does not exist")?;
+
+        game.players.iter().chain(game.spectators.iter()).map(|p| game.viewer(p)).collect()
+    };
+
+    let sessions = ctx.sessions.lock().unwrap();
+
+    for viewer in members {
+        let member = &viewer.player;
+
+        if member == sender {
+            continue;
+        }
+
+        let member_session = match sessions.get_session(member) {
+            Some(member_session) => member_session,
+            None => continue
+        };
+
+        let interest = member_session.interest.as_ref();
+        let entries = changes.iter()
+                             .filter(|c| member_session.is_subscribed_to(&c.entry.name))
+                             .filter_map(|c| c.entry_for(interest, &viewer));
+
+        for entry in entries {
+            let notification = NotifyMessage::from_info(sequence, entry);
+            if let Err(error) = send_read_reply(&member_session, &viewer, &notification.serialize()) {
+                log_error(&error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Get the name of the player, which is only known once the HELLO message was
+ * received.
+ */
+fn get_player_name(session: &PlayerSession) -> Result<String, String> {
+    session.player_name.clone().ok_or_else(|| String::from("Session has no player name, HELLO first"))
+}
+
+fn get_game_name(session: &PlayerSession) -> Result<String, String> {
+    session.game.clone().ok_or_else(|| String::from("Player is not in a game session"))
 }
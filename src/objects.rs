@@ -0,0 +1,2589 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::convert::TryInto;
+use chrono::prelude::*;
+use crate::commands::{ObjProperties, PutObjCommand, PutOperation, MAX_TTL, ObjectEntry, EntryStatus, PropertyValue, ValueType, OwnerLeavePolicy, RewindMode, QueryCommand, Comparison, IndexKind, SpawnCommand, Visibility};
+use crate::indexes::{IndexKey, PropertyIndex};
+use crate::spatial::{Area, Position, SpatialGrid};
+use crate::schemas::{ClassSchema, ObjectTemplate};
+use crate::utils::{buf_to_u64, glob_matches};
+
+pub type ObjectId = u64;
+
+// Objects with a property of this name are placed in the spatial index. The
+// property starts with the x and y coordinates as two little endian f64, like
+// Vec2 and Vec3 values.
+pub static POSITION_PROPERTY: &str = "Position";
+
+// Number of deleted objects remembered for delta sync. Clients asking for
+// changes older than the oldest forgotten delete get the full world instead.
+static MAX_TOMBSTONES: usize = 4096;
+
+// Number of past states kept for each object, whatever the history duration.
+static MAX_HISTORY_ENTRIES: usize = 1024;
+
+// First id handed out by the server. Clients can only add objects with ids
+// from this one on when they leased them.
+pub static SERVER_ID_START: ObjectId = 1 << 48;
+
+// Most blocks of ids a player can lease at once.
+pub static MAX_LEASES: usize = 16;
+
+/**
+ * A game object replicated by the server and its properties.
+ *
+ * Each change to the object increments its version, and each property keeps
+ * the version of the object at which it last changed. Objects and properties
+ * also keep the sequence number of the world change that last touched them.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredObject {
+    pub id: ObjectId,
+    pub name: String,
+    // Empty for objects without a class.
+    pub class: String,
+    // Player allowed to modify the object along with the host, empty for
+    // objects added by the server.
+    pub owner: String,
+    pub on_owner_leave: OwnerLeavePolicy,
+    // Seconds without writes after which the object is deleted, 0 for
+    // objects that do not expire.
+    pub ttl: u64,
+    pub expires_at: Option<DateTime<UTC>>,
+    // Object this one belongs to, 0 when it has no parent.
+    pub parent_id: ObjectId,
+    pub version: u64,
+    pub created_at: u64,
+    pub changed_at: u64,
+    pub properties: Vec<StoredProperty>,
+    // Position of the object before its last change.
+    pub previous_position: Option<Position>,
+    // Position of the object before the last change of its position, and the
+    // sequence number it was set at.
+    pub position_before_move: Option<Position>,
+    pub position_before_move_at: u64,
+    // Past states of the object, oldest first.
+    pub history: VecDeque<HistoryEntry>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredProperty {
+    pub value: ObjProperties,
+    pub version: u64,
+    pub changed_at: u64
+}
+
+/**
+ * State of an object after a change, kept for REWIND queries.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub at: DateTime<UTC>,
+    pub version: u64,
+    pub properties: Vec<ObjProperties>
+}
+
+/**
+ * Remembers a deleted object so clients syncing deltas learn about it.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tombstone {
+    pub id: ObjectId,
+    pub name: String,
+    pub version: u64,
+    pub changed_at: u64,
+    pub position: Option<Position>
+}
+
+impl Tombstone {
+    pub fn to_entry(&self) -> ObjectEntry {
+        ObjectEntry {
+            status: EntryStatus::Deleted,
+            object_id: self.id,
+            version: self.version,
+            parent_id: 0,
+            name: self.name.clone(),
+            properties: vec![]
+        }
+    }
+}
+
+/**
+ * A client receiving objects, used to leave out the properties it is not
+ * allowed to see. See the visibility of properties in CLASS.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Viewer {
+    pub player: String,
+    // Players on the team of the viewer, itself included. Empty when it is
+    // not on a team.
+    pub team: Vec<String>,
+    // Set for the host, which is the source of truth of the world.
+    pub sees_all: bool,
+    // Seconds objects are sent late to the viewer, set for spectators.
+    pub delay: u64
+}
+
+impl Viewer {
+    /**
+     * A viewer seeing every property.
+     */
+    pub fn everything() -> Viewer {
+        Viewer { sees_all: true, ..Viewer::default() }
+    }
+
+    /**
+     * Checks if the viewer can see a property of an object owned by a
+     * player.
+     */
+    pub fn can_see(&self, visibility: Visibility, owner: &str) -> bool {
+        match visibility {
+            _ if self.sees_all => true,
+            Visibility::Public => true,
+            Visibility::Owner => owner == self.player,
+            Visibility::Team => owner == self.player || self.team.iter().any(|p| p == owner)
+        }
+    }
+}
+
+/**
+ * A change made to an object, used to decide what each client receives.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectChange {
+    // Changed properties, or the tombstone of a deleted object.
+    pub entry: ObjectEntry,
+    // Every property of the object, None when it was deleted.
+    pub full: Option<ObjectEntry>,
+    pub created: bool,
+    pub position: Option<Position>,
+    pub previous_position: Option<Position>,
+    pub owner: String,
+    // Properties of the object which are not public, from its class.
+    pub restricted: Vec<(String, Visibility)>
+}
+
+impl ObjectChange {
+    /**
+     * Entry to send to a client watching an area of the world, or to every
+     * client when the area is None. Objects without a position are sent to
+     * everyone.
+     *
+     * Objects moving into the area are sent with all their properties and
+     * the Entered status, objects moving out of it are sent with the Left
+     * status and no properties.
+     *
+     * Properties the viewer cannot see are left out, updates of such
+     * properties only are not sent at all. Objects whose position the viewer
+     * cannot see are handled as objects without position.
+     */
+    pub fn entry_for(&self, area: Option<&Area>, viewer: &Viewer) -> Option<ObjectEntry> {
+        let area = area.filter(|_| self.can_see(POSITION_PROPERTY, viewer));
+        let mut entry = self.area_entry(area)?;
+        let is_update = entry.status == EntryStatus::Found && !self.created && !entry.properties.is_empty();
+
+        entry.properties.retain(|p| self.can_see(&p.name, viewer));
+
+        if is_update && entry.properties.is_empty() {
+            return None;
+        }
+
+        Some(entry)
+    }
+
+    fn can_see(&self, property_id: &str, viewer: &Viewer) -> bool {
+        self.restricted.iter()
+                       .find(|(id, _)| id == property_id)
+                       .is_none_or(|(_, visibility)| viewer.can_see(*visibility, &self.owner))
+    }
+
+    fn area_entry(&self, area: Option<&Area>) -> Option<ObjectEntry> {
+        let (area, position) = match (area, self.position) {
+            (Some(area), Some(position)) => (area, position),
+            _ => return Some(self.entry.clone())
+        };
+
+        let is_inside = area.contains(position);
+
+        let full = match &self.full {
+            Some(full) => full,
+            None => return if is_inside { Some(self.entry.clone()) } else { None }
+        };
+
+        // Objects that had no position were sent to everyone before.
+        let was_inside = !self.created && self.previous_position.is_none_or(|p| area.contains(p));
+
+        match (was_inside, is_inside) {
+            (true, true) => Some(self.entry.clone()),
+            (false, true) if self.created => Some(self.entry.clone()),
+            (false, true) => Some(ObjectEntry { status: EntryStatus::Entered, ..full.clone() }),
+            (true, false) => Some(ObjectEntry { status: EntryStatus::Left, properties: vec![], ..full.clone() }),
+            (false, false) => None
+        }
+    }
+}
+
+/**
+ * Checks if an object is visible to a client watching an area. Objects
+ * without position and clients without area see everything.
+ */
+fn is_visible_in(area: Option<&Area>, position: Option<Position>) -> bool {
+    match (area, position) {
+        (Some(area), Some(position)) => area.contains(position),
+        _ => true
+    }
+}
+
+/**
+ * Read the position of an object from its position property.
+ */
+pub fn decode_position(property: &ObjProperties) -> Option<Position> {
+    let data = &property.data;
+
+    if data.len() < 16 {
+        return None;
+    }
+
+    let x = f64::from_bits(buf_to_u64(data[0..8].try_into().unwrap()));
+    let y = f64::from_bits(buf_to_u64(data[8..16].try_into().unwrap()));
+
+    if x.is_finite() && y.is_finite() {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+impl StoredObject {
+    pub fn get_property(&self, name: &str) -> Option<&ObjProperties> {
+        self.properties.iter().find(|p| p.value.name == name).map(|p| &p.value)
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.get_property(POSITION_PROPERTY).and_then(decode_position)
+    }
+
+    /**
+     * Sequence number the position of the object was last set at, or the one
+     * the object was created at when it never had a position.
+     */
+    fn moved_at(&self) -> u64 {
+        self.properties.iter()
+                       .find(|p| p.value.name == POSITION_PROPERTY)
+                       .map_or(self.created_at, |p| p.changed_at)
+    }
+
+    /**
+     * Position the object had at a past sequence number, None when it moved
+     * more than once since then and the position is not known anymore.
+     */
+    fn position_at_sequence(&self, sequence: u64) -> Option<Option<Position>> {
+        if self.moved_at() <= sequence {
+            Some(self.position())
+        } else if self.position_before_move_at <= sequence {
+            Some(self.position_before_move)
+        } else {
+            None
+        }
+    }
+
+    pub fn property_version(&self, name: &str) -> Option<u64> {
+        self.properties.iter().find(|p| p.value.name == name).map(|p| p.version)
+    }
+
+    /**
+     * Create the entry sent to clients for this object. Only the properties
+     * listed are included, or all of them when the list is empty.
+     */
+    pub fn to_entry(&self, property_names: &[String]) -> ObjectEntry {
+        let properties = self.properties.iter()
+                                         .map(|p| &p.value)
+                                         .filter(|p| property_names.is_empty() || property_names.contains(&p.name))
+                                         .cloned()
+                                         .collect();
+
+        ObjectEntry {
+            status: EntryStatus::Found,
+            object_id: self.id,
+            version: self.version,
+            parent_id: self.parent_id,
+            name: self.name.clone(),
+            properties
+        }
+    }
+
+    /**
+     * Create the entry sent to clients syncing changes made after a sequence
+     * number. Only the properties changed after it are included.
+     */
+    pub fn to_delta_entry(&self, since: u64) -> ObjectEntry {
+        let mut entry = self.to_entry(&[]);
+
+        entry.properties = self.properties.iter()
+                                          .filter(|p| p.changed_at > since)
+                                          .map(|p| p.value.clone())
+                                          .collect();
+
+        entry
+    }
+
+    /**
+     * Record the current state of the object. States older than the history
+     * duration are dropped, except the last one which is still the state of
+     * the object at the start of the history. At most MAX_HISTORY_ENTRIES
+     * states are kept, the oldest ones going first.
+     */
+    fn record_history(&mut self, now: DateTime<UTC>, history_ms: u64) {
+        self.history.push_back(HistoryEntry {
+            at: now,
+            version: self.version,
+            properties: self.properties.iter().map(|p| p.value.clone()).collect()
+        });
+
+        let cutoff = history_ms.try_into().ok()
+                               .and_then(|ms: i64| now.checked_sub_signed(chrono::Duration::milliseconds(ms)));
+
+        while self.history.len() > 1 && cutoff.is_some_and(|cutoff| self.history[1].at <= cutoff) {
+            self.history.pop_front();
+        }
+
+        while self.history.len() > MAX_HISTORY_ENTRIES {
+            self.history.pop_front();
+        }
+    }
+
+    /**
+     * Rebuild the entry of the object as it was at a past time. Returns None
+     * when no state was recorded at that time. Only the properties the class
+     * of the object declares as Float, Vec2 or Vec3 can be interpolated, the
+     * others are taken from the nearest state holding them.
+     */
+    pub fn state_at(&self, at: DateTime<UTC>, mode: RewindMode, property_names: &[String], schema: Option<&ClassSchema>) -> Option<ObjectEntry> {
+        let (before, after) = match self.history.iter().position(|h| h.at > at) {
+            Some(0) => return None,
+            Some(index) => (&self.history[index - 1], Some(&self.history[index])),
+            None => (self.history.back()?, None)
+        };
+
+        let is_after_nearest = |after: &HistoryEntry| after.at.signed_duration_since(at) < at.signed_duration_since(before.at);
+
+        let (version, properties) = match (mode, after) {
+            (_, None) => (before.version, before.properties.clone()),
+            (RewindMode::Nearest, Some(after)) => {
+                let nearest = if is_after_nearest(after) { after } else { before };
+
+                (nearest.version, nearest.properties.clone())
+            },
+            (RewindMode::Interpolate, Some(after)) => {
+                let span = after.at.signed_duration_since(before.at).num_milliseconds();
+                let ratio = if span > 0 { at.signed_duration_since(before.at).num_milliseconds() as f64 / span as f64 } else { 0.0 };
+
+                let value_type = |name: &str| schema.map_or(ValueType::Raw, |s| s.value_type(name));
+
+                let mut properties: Vec<ObjProperties> = before.properties.iter().map(|p| {
+                    let next = match after.properties.iter().find(|n| n.name == p.name) {
+                        Some(next) => next,
+                        None => return p.clone()
+                    };
+
+                    let interpolated = match (p.value_as(value_type(&p.name)), next.value_as(value_type(&p.name))) {
+                        (Ok(from), Ok(to)) => from.interpolate(&to, ratio),
+                        _ => None
+                    };
+
+                    match interpolated {
+                        Some(value) => ObjProperties::typed(&p.name, &value),
+                        None if is_after_nearest(after) => next.clone(),
+                        None => p.clone()
+                    }
+                }).collect();
+
+                // Properties added between the two states.
+                properties.extend(after.properties.iter().filter(|n| before.properties.iter().all(|p| p.name != n.name)).cloned());
+
+                (before.version, properties)
+            }
+        };
+
+        Some(ObjectEntry {
+            status: EntryStatus::Found,
+            object_id: self.id,
+            version,
+            parent_id: self.parent_id,
+            name: self.name.clone(),
+            properties: properties.into_iter().filter(|p| property_names.is_empty() || property_names.contains(&p.name)).collect()
+        })
+    }
+
+    /**
+     * Check the version a client expects the object to be at. An expected
+     * version of 0 skips the check.
+     */
+    fn check_version(&self, expected_version: u64) -> Result<(), StoreError> {
+        if expected_version != 0 && expected_version != self.version {
+            return Err(StoreError::VersionConflict { id: self.id, expected: expected_version, current: self.version });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Replace the properties with the same name and add the others, then
+     * move the object to its next version.
+     */
+    fn merge_properties(&mut self, properties: &[ObjProperties], sequence: u64) {
+        self.version += 1;
+        self.changed_at = sequence;
+        self.previous_position = self.position();
+
+        if properties.iter().any(|p| p.name == POSITION_PROPERTY) {
+            self.position_before_move = self.position();
+            self.position_before_move_at = self.moved_at();
+        }
+
+        for prop in properties {
+            let stored = StoredProperty {
+                value: prop.clone(),
+                version: self.version,
+                changed_at: sequence
+            };
+
+            match self.properties.iter_mut().find(|p| p.value.name == prop.name) {
+                Some(existing) => *existing = stored,
+                None => self.properties.push(stored)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StoreError {
+    AlreadyExists(ObjectId),
+    NameTaken(String),
+    NotFound(ObjectId),
+    VersionConflict { id: ObjectId, expected: u64, current: u64 },
+    InvalidParent { id: ObjectId, parent: ObjectId },
+    NoHistory(ObjectId),
+    InvalidValue(String),
+    SchemaViolation(String),
+    NotApplied { id: ObjectId, property: String },
+    IdLeased { id: ObjectId, player: String },
+    IdReserved(ObjectId),
+    IdsExhausted,
+    TooManyLeases(String),
+    TemplateNotFound(String),
+    StaleTemplate { name: String, expected: u64, current: u64 }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::AlreadyExists(id) => write!(f, "Object {} already exists", id),
+            StoreError::NameTaken(name) => write!(f, "An object named {} already exists", name),
+            StoreError::NotFound(id) => write!(f, "Object {} does not exist", id),
+            StoreError::VersionConflict { id, expected, current } => {
+                write!(f, "Object {} is at version {}, expected version {}", id, current, expected)
+            },
+            StoreError::InvalidParent { id, parent } => write!(f, "Object {} cannot be the parent of object {}", parent, id),
+            StoreError::NoHistory(id) => write!(f, "No state of object {} was recorded at that time", id),
+            StoreError::InvalidValue(error) | StoreError::SchemaViolation(error) => write!(f, "{}", error),
+            StoreError::NotApplied { id, property } => write!(f, "Operation on property {} of object {} was not applied", property, id),
+            StoreError::IdLeased { id, player } => write!(f, "Object id {} is leased by {}", id, player),
+            StoreError::IdReserved(id) => write!(f, "Object id {} is reserved for the server", id),
+            StoreError::IdsExhausted => write!(f, "No object ids left"),
+            StoreError::TooManyLeases(player) => write!(f, "Player {} cannot lease more than {} blocks of ids", player, MAX_LEASES),
+            StoreError::TemplateNotFound(name) => write!(f, "Template {} does not exist", name),
+            StoreError::StaleTemplate { name, expected, current } => {
+                write!(f, "Template {} is at version {}, expected version {}", name, current, expected)
+            }
+        }
+    }
+}
+
+/**
+ * Outcome of an atomic operation on a property. The value is the property
+ * after the operation, or its current value when the operation was not
+ * applied.
+ */
+#[derive(Debug, PartialEq)]
+pub struct AtomicResult {
+    pub version: u64,
+    pub applied: bool,
+    pub value: Option<ObjProperties>
+}
+
+/**
+ * The objects of a single game session.
+ *
+ * Every change made to the world increments its sequence number, clients use
+ * it to fetch only what changed since the last time they synced.
+ *
+ * The secondary indexes are updated along with the objects by every method
+ * changing them. The world is only changed behind the lock of the object
+ * store, so queries never see an index out of date.
+ */
+#[derive(Clone, Default)]
+pub struct World {
+    objects: BTreeMap<ObjectId, StoredObject>,
+    tombstones: BTreeMap<ObjectId, Tombstone>,
+    grid: SpatialGrid,
+    sequence: u64,
+    // Oldest sequence number a delta can be computed from.
+    delta_floor: u64,
+    classes: HashMap<String, ClassSchema>,
+    // Indexes declared by each class, by property id.
+    indexes: HashMap<String, HashMap<String, PropertyIndex>>,
+    // Children of each object that has some.
+    children: HashMap<ObjectId, BTreeSet<ObjectId>>,
+    // Objects of each player.
+    owner_index: PropertyIndex,
+    // Next id the server can hand out, ids are never handed out twice.
+    next_id: ObjectId,
+    // Blocks of ids leased by players, by first id, with their end excluded
+    // and the player holding them.
+    leases: BTreeMap<ObjectId, (ObjectId, String)>,
+    templates: BTreeMap<String, ObjectTemplate>,
+    // Milliseconds of past states kept for each object, 0 keeps none.
+    history_ms: u64,
+    // Set while a transaction is applied, see apply_all.
+    undo: Option<UndoLog>
+}
+
+/**
+ * What a transaction changed in a world, kept to undo it when one of its
+ * commands fails.
+ */
+#[derive(Clone, Default)]
+struct UndoLog {
+    // Objects and tombstones before their first change, None when they did
+    // not exist.
+    objects: HashMap<ObjectId, Option<StoredObject>>,
+    tombstones: HashMap<ObjectId, Option<Tombstone>>,
+    sequence: u64,
+    delta_floor: u64,
+    next_id: ObjectId
+}
+
+/**
+ * Compute the value of a property after an atomic operation, see PUTOBJ for
+ * the operands. Returns None when the operation must not be applied.
+ */
+fn atomic_value(operation: PutOperation, current: Option<PropertyValue>, operands: &[PropertyValue]) -> Result<Option<PropertyValue>, StoreError> {
+    let invalid = |message: &str| StoreError::InvalidValue(format!("{:?} {}", operation, message));
+    let operand = |index: usize| operands.get(index).ok_or_else(|| invalid(&format!("needs {} operands", index + 1)));
+
+    let value = match operation {
+        PutOperation::Increment | PutOperation::Decrement => {
+            let delta = operand(0)?;
+            let start = current.unwrap_or(match delta {
+                PropertyValue::Float(_) => PropertyValue::Float(0.0),
+                _ => PropertyValue::Int(0)
+            });
+
+            if start.value_type() != delta.value_type() || start.as_f64().is_none() {
+                return Err(invalid("needs an Int or Float property and a delta of the same type"));
+            }
+
+            let value = match operation {
+                PutOperation::Increment => start.add(delta),
+                _ => start.subtract(delta)
+            };
+
+            // Overflows are treated like crossing a bound.
+            let value = match value {
+                Some(value) => value,
+                None => return Ok(None)
+            };
+
+            let min = operands.get(1).map(|min| value.compare(min).ok_or_else(|| invalid("bounds must be numbers"))).transpose()?;
+            let max = operands.get(2).map(|max| value.compare(max).ok_or_else(|| invalid("bounds must be numbers"))).transpose()?;
+
+            if min == Some(Ordering::Less) || max == Some(Ordering::Greater) {
+                return Ok(None);
+            }
+
+            Some(value)
+        },
+        PutOperation::CompareAndSwap => {
+            let (expected, replacement) = (operand(0)?, operand(1)?);
+
+            if current.as_ref() == Some(expected) { Some(replacement.clone()) } else { None }
+        },
+        PutOperation::Append => {
+            let item = operand(0)?.clone();
+
+            match current {
+                None => Some(PropertyValue::List(vec![item])),
+                Some(PropertyValue::List(mut items)) => {
+                    items.push(item);
+
+                    Some(PropertyValue::List(items))
+                },
+                Some(_) => return Err(invalid("needs a List property"))
+            }
+        },
+        PutOperation::Remove => {
+            let item = operand(0)?;
+
+            match current {
+                None => None,
+                Some(PropertyValue::List(mut items)) => {
+                    items.iter().position(|i| i == item).map(|index| {
+                        items.remove(index);
+
+                        PropertyValue::List(items)
+                    })
+                },
+                Some(_) => return Err(invalid("needs a List property"))
+            }
+        },
+        PutOperation::SetIfAbsent => {
+            let value = operand(0)?;
+
+            if current.is_none() { Some(value.clone()) } else { None }
+        },
+        PutOperation::Add | PutOperation::Update | PutOperation::Delete | PutOperation::Reparent => {
+            return Err(invalid("is not an atomic operation"));
+        }
+    };
+
+    Ok(value)
+}
+
+/**
+ * Order the sort values of two objects in a query. Numbers come first, then
+ * strings, then the values that cannot be ordered, and objects without the
+ * property come last whatever the direction.
+ */
+fn sort_order(a: &Option<PropertyValue>, b: &Option<PropertyValue>, descending: bool) -> Ordering {
+    let rank = |value: &PropertyValue| match value {
+        PropertyValue::Str(_) => 1,
+        _ if value.as_f64().is_some_and(|v| !v.is_nan()) => 0,
+        _ => 2
+    };
+
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let order = rank(a).cmp(&rank(b)).then_with(|| a.compare(b).unwrap_or(Ordering::Equal));
+
+            if descending { order.reverse() } else { order }
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal
+    }
+}
+
+impl World {
+    /**
+     * Add a new object. Object ids are unique, and so are the names of named
+     * objects.
+     */
+    pub fn add(&mut self, id: ObjectId, name: &str, properties: &[ObjProperties]) -> Result<&StoredObject, StoreError> {
+        self.add_with_class(id, name, "", properties)
+    }
+
+    /**
+     * Add a new object of a registered class, its properties are checked
+     * against the class schema.
+     */
+    pub fn add_with_class(&mut self, id: ObjectId, name: &str, class: &str, properties: &[ObjProperties]) -> Result<&StoredObject, StoreError> {
+        if self.objects.contains_key(&id) {
+            return Err(StoreError::AlreadyExists(id));
+        }
+
+        if !name.is_empty() && self.find_by_name(name).is_some() {
+            return Err(StoreError::NameTaken(String::from(name)));
+        }
+
+        self.validate_class(class, properties, true)?;
+
+        self.save_object(id);
+        self.save_tombstone(id);
+
+        // An object added back under a deleted id starts from the version
+        // of the deleted object so versions keep increasing.
+        let version = self.tombstones.remove(&id).map_or(0, |t| t.version);
+
+        let sequence = self.next_sequence();
+
+        let object = StoredObject {
+            id,
+            name: String::from(name),
+            class: String::from(class),
+            owner: String::new(),
+            on_owner_leave: OwnerLeavePolicy::RevertToHost,
+            ttl: 0,
+            expires_at: None,
+            parent_id: 0,
+            version,
+            created_at: sequence,
+            changed_at: 0,
+            properties: vec![],
+            previous_position: None,
+            position_before_move: None,
+            position_before_move_at: sequence,
+            history: VecDeque::new()
+        };
+
+        let object = self.objects.entry(id).or_insert(object);
+        object.merge_properties(properties, sequence);
+
+        World::index_position(&mut self.grid, object);
+        self.reindex(id);
+
+        Ok(&self.objects[&id])
+    }
+
+    /**
+     * Update the properties of an existing object. Properties that are not
+     * part of the update keep their value.
+     *
+     * The update is rejected if the object is not at the expected version,
+     * unless the expected version is 0.
+     */
+    pub fn update(&mut self, id: ObjectId, properties: &[ObjProperties], expected_version: u64) -> Result<&StoredObject, StoreError> {
+        let object = self.objects.get(&id).ok_or(StoreError::NotFound(id))?;
+
+        object.check_version(expected_version)?;
+        self.validate_class(&object.class, properties, false)?;
+
+        self.save_object(id);
+
+        let sequence = self.next_sequence();
+        let object = self.objects.get_mut(&id).unwrap();
+        object.merge_properties(properties, sequence);
+
+        World::index_position(&mut self.grid, object);
+        self.reindex(id);
+
+        Ok(&self.objects[&id])
+    }
+
+    /**
+     * Delete an object and all its descendants. Only the version of the
+     * object itself is checked.
+     */
+    pub fn delete(&mut self, id: ObjectId, expected_version: u64) -> Result<StoredObject, StoreError> {
+        let object = self.objects.get(&id).ok_or(StoreError::NotFound(id))?;
+
+        object.check_version(expected_version)?;
+
+        let sequence = self.next_sequence();
+
+        for descendant in self.descendants(id) {
+            self.remove_object(descendant, sequence);
+        }
+
+        Ok(self.remove_object(id, sequence))
+    }
+
+    /**
+     * Move an object under another parent, or to the top level when the
+     * parent is 0. An object cannot be moved under one of its descendants.
+     */
+    pub fn reparent(&mut self, id: ObjectId, parent_id: ObjectId, expected_version: u64) -> Result<&StoredObject, StoreError> {
+        let object = self.objects.get(&id).ok_or(StoreError::NotFound(id))?;
+
+        object.check_version(expected_version)?;
+        self.check_parent(id, parent_id)?;
+
+        self.save_object(id);
+
+        let sequence = self.next_sequence();
+        self.objects.get_mut(&id).unwrap().merge_properties(&[], sequence);
+        self.set_parent(id, parent_id);
+
+        Ok(&self.objects[&id])
+    }
+
+    /**
+     * Change the parent of an object, keeping the children map in sync.
+     */
+    fn set_parent(&mut self, id: ObjectId, parent_id: ObjectId) {
+        let object = self.objects.get_mut(&id).unwrap();
+        let previous = object.parent_id;
+        object.parent_id = parent_id;
+
+        self.unlink_child(previous, id);
+        self.link_child(parent_id, id);
+    }
+
+    fn link_child(&mut self, parent_id: ObjectId, id: ObjectId) {
+        if parent_id != 0 {
+            self.children.entry(parent_id).or_default().insert(id);
+        }
+    }
+
+    fn unlink_child(&mut self, parent_id: ObjectId, id: ObjectId) {
+        if let Some(children) = self.children.get_mut(&parent_id) {
+            children.remove(&id);
+
+            if children.is_empty() {
+                self.children.remove(&parent_id);
+            }
+        }
+    }
+
+    pub fn children(&self, id: ObjectId) -> Vec<&StoredObject> {
+        self.children.get(&id)
+            .map(|children| children.iter().map(|child| &self.objects[child]).collect())
+            .unwrap_or_default()
+    }
+
+    /**
+     * List the ids of every descendant of an object, parents before their
+     * children. The tree is walked without recursion since it can be
+     * arbitrarily deep.
+     */
+    pub fn descendants(&self, id: ObjectId) -> Vec<ObjectId> {
+        let mut result = vec![];
+        let mut pending = vec![id];
+
+        while let Some(parent_id) = pending.pop() {
+            if let Some(children) = self.children.get(&parent_id) {
+                // Pushed in reverse so that siblings come out in id order.
+                for child in children.iter().rev() {
+                    pending.push(*child);
+                }
+            }
+
+            if parent_id != id {
+                result.push(parent_id);
+            }
+        }
+
+        result
+    }
+
+    fn check_parent(&self, id: ObjectId, parent_id: ObjectId) -> Result<(), StoreError> {
+        if parent_id == 0 {
+            return Ok(());
+        }
+
+        let is_valid = parent_id != id && self.objects.contains_key(&parent_id) && !self.descendants(id).contains(&parent_id);
+
+        if !is_valid {
+            return Err(StoreError::InvalidParent { id, parent: parent_id });
+        }
+
+        Ok(())
+    }
+
+    fn remove_object(&mut self, id: ObjectId, sequence: u64) -> StoredObject {
+        self.save_object(id);
+
+        let object = self.objects.remove(&id).unwrap();
+
+        self.grid.remove(id);
+        self.unindex(&object);
+        self.unlink_child(object.parent_id, id);
+
+        self.add_tombstone(Tombstone {
+            id,
+            name: object.name.clone(),
+            version: object.version + 1,
+            changed_at: sequence,
+            // Deletes of objects with a restricted position are sent to
+            // everyone rather than telling where the object was.
+            position: object.position().filter(|_| self.restricted_properties(&object.class).iter().all(|(id, _)| id != POSITION_PROPERTY))
+        });
+
+        object
+    }
+
+    /**
+     * List the objects created, updated or deleted after a sequence number.
+     * Updated objects only contain the properties that changed, deleted
+     * objects are marked as such.
+     *
+     * When an area is given, only the objects inside it or without position
+     * are returned. Changed objects now outside the area are sent with the
+     * Left status, and objects inside it whose position changed are sent with
+     * all their properties and the Entered status since the client may not
+     * know them.
+     *
+     * Returns true along with the entries when the sequence number is too old
+     * to compute a delta. All objects are then returned and the client must
+     * replace its state with them.
+     *
+     * Properties the viewer cannot see are left out of every entry, objects
+     * whose position the viewer cannot see are handled as objects without
+     * position.
+     */
+    pub fn changes_since(&self, since: u64, area: Option<&Area>, viewer: &Viewer) -> (Vec<ObjectEntry>, bool) {
+        if since < self.delta_floor {
+            let entries = self.objects.values()
+                                      .filter(|o| is_visible_in(area, self.visible_position(o, viewer)))
+                                      .map(|o| self.visible_entry(o, o.to_entry(&[]), viewer))
+                                      .collect();
+
+            return (entries, true);
+        }
+
+        let mut entries = vec![];
+
+        for object in self.objects.values().filter(|o| o.changed_at > since) {
+            let position = match (area, self.visible_position(object, viewer)) {
+                (Some(area), Some(position)) => (area, position),
+                _ => {
+                    entries.push(self.visible_entry(object, object.to_delta_entry(since), viewer));
+
+                    continue;
+                }
+            };
+
+            let (area, position) = position;
+            let is_inside = area.contains(position);
+
+            // Objects created since the last sync were never seen by the
+            // client. Objects whose position at the last sync is not known
+            // anymore may have been seen, as well as objects without position.
+            let was_inside = object.created_at <= since
+                && object.position_at_sequence(since).is_none_or(|p| p.is_none_or(|p| area.contains(p)));
+
+            match (was_inside, is_inside) {
+                (false, true) if object.created_at <= since => {
+                    entries.push(self.visible_entry(object, ObjectEntry { status: EntryStatus::Entered, ..object.to_entry(&[]) }, viewer));
+                },
+                (_, true) => entries.push(self.visible_entry(object, object.to_delta_entry(since), viewer)),
+                (true, false) => entries.push(ObjectEntry { status: EntryStatus::Left, properties: vec![], ..object.to_entry(&[]) }),
+                (false, false) => {}
+            }
+        }
+
+        let deleted = self.tombstones.values()
+                                     .filter(|t| t.changed_at > since)
+                                     .filter(|t| is_visible_in(area, t.position));
+
+        entries.extend(deleted.map(|t| t.to_entry()));
+
+        (entries, false)
+    }
+
+    /**
+     * List the changes made after a sequence number with what is needed to
+     * filter them for each client.
+     */
+    pub fn change_set(&self, since: u64) -> Vec<ObjectChange> {
+        let mut changes: Vec<ObjectChange> = self.objects.values().filter(|o| o.changed_at > since).map(|o| {
+            ObjectChange {
+                entry: o.to_delta_entry(since),
+                full: Some(o.to_entry(&[])),
+                created: o.created_at > since,
+                position: o.position(),
+                previous_position: o.previous_position,
+                owner: o.owner.clone(),
+                restricted: self.restricted_properties(&o.class)
+            }
+        }).collect();
+
+        changes.extend(self.tombstones.values().filter(|t| t.changed_at > since).map(|t| {
+            ObjectChange {
+                entry: t.to_entry(),
+                full: None,
+                created: false,
+                position: t.position,
+                previous_position: t.position,
+                owner: String::new(),
+                restricted: vec![]
+            }
+        }));
+
+        changes
+    }
+
+    /**
+     * List the objects inside an area of the world whose position the viewer
+     * can see, sorted by id.
+     */
+    pub fn objects_in(&self, area: &Area, viewer: &Viewer) -> Vec<&StoredObject> {
+        self.grid.query(area).iter()
+                 .filter_map(|id| self.objects.get(id))
+                 .filter(|o| self.can_see(o, POSITION_PROPERTY, viewer))
+                 .collect()
+    }
+
+    /**
+     * Every object visible in an area with all the properties the viewer
+     * can see, sorted by id. Objects without position, or whose position the
+     * viewer cannot see, are always included.
+     */
+    pub fn snapshot(&self, area: Option<&Area>, viewer: &Viewer) -> Vec<ObjectEntry> {
+        self.objects.values()
+                    .filter(|o| is_visible_in(area, self.visible_position(o, viewer)))
+                    .map(|o| self.visible_entry(o, o.to_entry(&[]), viewer))
+                    .collect()
+    }
+
+    /**
+     * List the objects that have a position the viewer can see, sorted by id.
+     */
+    pub fn positioned_objects(&self, viewer: &Viewer) -> Vec<&StoredObject> {
+        self.objects.values().filter(|o| self.visible_position(o, viewer).is_some()).collect()
+    }
+
+    /**
+     * Position of an object, None when it has none or when the viewer cannot
+     * see it.
+     */
+    fn visible_position(&self, object: &StoredObject, viewer: &Viewer) -> Option<Position> {
+        object.position().filter(|_| self.can_see(object, POSITION_PROPERTY, viewer))
+    }
+
+    fn index_position(grid: &mut SpatialGrid, object: &StoredObject) {
+        match object.position() {
+            Some(position) => grid.insert(object.id, position),
+            None => grid.remove(object.id)
+        }
+    }
+
+    /**
+     * Sequence number of the last change made to the world.
+     */
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+
+        self.sequence
+    }
+
+    fn add_tombstone(&mut self, tombstone: Tombstone) {
+        self.save_tombstone(tombstone.id);
+        self.tombstones.insert(tombstone.id, tombstone);
+
+        if self.tombstones.len() > MAX_TOMBSTONES {
+            let oldest = self.tombstones.values().min_by_key(|t| t.changed_at).unwrap().clone();
+
+            self.save_tombstone(oldest.id);
+            self.tombstones.remove(&oldest.id);
+            self.delta_floor = oldest.changed_at;
+        }
+    }
+
+    /**
+     * Apply a PUTOBJ command sent by a player to the world. Returns the id
+     * and the version of the object after the change, deleting an object
+     * moves it to a last version. Objects added with an id of 0 get an id
+     * chosen by the server. Added objects are owned by the player, and writes
+     * refresh the expiry of objects with a ttl.
+     */
+    pub fn apply(&mut self, command: &PutObjCommand, player: &str, now: DateTime<UTC>) -> Result<(ObjectId, u64), StoreError> {
+        let id = match command.operation {
+            PutOperation::Add => {
+                if command.ttl > MAX_TTL {
+                    return Err(StoreError::InvalidValue(format!("Ttl cannot be above {} seconds", MAX_TTL)));
+                }
+
+                let id = match command.object_id {
+                    0 => self.allocate_ids(1)?,
+                    id => {
+                        match self.lease_holder(id) {
+                            Some(holder) if holder != player => {
+                                return Err(StoreError::IdLeased { id, player: String::from(holder) });
+                            },
+                            None if id >= SERVER_ID_START => return Err(StoreError::IdReserved(id)),
+                            _ => id
+                        }
+                    }
+                };
+
+                self.check_parent(id, command.parent_id)?;
+
+                self.add_with_class(id, &command.object_name, &command.class_name, &command.properties)?;
+
+                self.set_parent(id, command.parent_id);
+
+                let object = self.objects.get_mut(&id).unwrap();
+                object.owner = String::from(player);
+                object.on_owner_leave = command.on_owner_leave;
+                object.ttl = command.ttl;
+
+                self.reindex(id);
+
+                id
+            },
+            PutOperation::Update => {
+                self.update(command.object_id, &command.properties, command.expected_version)?;
+
+                command.object_id
+            },
+            PutOperation::Delete => {
+                let deleted = self.delete(command.object_id, command.expected_version)?;
+
+                return Ok((deleted.id, deleted.version + 1));
+            },
+            PutOperation::Reparent => {
+                self.reparent(command.object_id, command.parent_id, command.expected_version)?;
+
+                command.object_id
+            },
+            _ => {
+                let result = self.apply_atomic(command, now)?;
+
+                if !result.applied {
+                    let property = command.properties.first().map_or(String::new(), |p| p.name.clone());
+
+                    return Err(StoreError::NotApplied { id: command.object_id, property });
+                }
+
+                return Ok((command.object_id, result.version));
+            }
+        };
+
+        Ok((id, self.touch(id, now)))
+    }
+
+    /**
+     * Apply an atomic operation to a property of an object. When the
+     * operation is not applied the object is left untouched, and the result
+     * holds the current value of the property.
+     */
+    pub fn apply_atomic(&mut self, command: &PutObjCommand, now: DateTime<UTC>) -> Result<AtomicResult, StoreError> {
+        let object = self.objects.get(&command.object_id).ok_or(StoreError::NotFound(command.object_id))?;
+
+        object.check_version(command.expected_version)?;
+
+        let name = command.properties.first().map(|p| p.name.clone()).ok_or_else(|| {
+            StoreError::InvalidValue(format!("{:?} needs a property", command.operation))
+        })?;
+
+        // List operands are items carrying their own type, the other operands
+        // have the type of the property.
+        let value_type = self.value_type(object, &name);
+
+        let operands = command.properties.iter()
+                                         .map(|p| match command.operation {
+                                             PutOperation::Append | PutOperation::Remove => PropertyValue::decode_item(&p.data),
+                                             _ => p.value_as(value_type)
+                                         })
+                                         .collect::<Result<Vec<PropertyValue>, String>>()
+                                         .map_err(StoreError::InvalidValue)?;
+
+        let current = object.get_property(&name).cloned();
+        let current_value = current.as_ref().map(|p| p.value_as(value_type)).transpose().map_err(StoreError::InvalidValue)?;
+
+        let property = match atomic_value(command.operation, current_value, &operands)? {
+            Some(value) => ObjProperties::typed(&name, &value),
+            None => return Ok(AtomicResult { version: object.version, applied: false, value: current })
+        };
+
+        self.update(command.object_id, std::slice::from_ref(&property), 0)?;
+
+        Ok(AtomicResult { version: self.touch(command.object_id, now), applied: true, value: Some(property) })
+    }
+
+    /**
+     * Refresh the expiry of an object after a write and record its new state
+     * in the history. Returns the version of the object.
+     */
+    fn touch(&mut self, id: ObjectId, now: DateTime<UTC>) -> u64 {
+        self.save_object(id);
+
+        let history_ms = self.history_ms;
+        let object = self.objects.get_mut(&id).unwrap();
+
+        if object.ttl > 0 {
+            object.expires_at = Some(now + chrono::Duration::seconds(object.ttl as i64));
+        }
+
+        if history_ms > 0 {
+            object.record_history(now, history_ms);
+        }
+
+        object.version
+    }
+
+    /**
+     * Apply several PUTOBJ commands, all of them or none. The state of what
+     * the commands change is saved in an undo log, and restored when one of
+     * them fails. Returns the object id and version after each command, or
+     * the index of the failing command and its error.
+     */
+    pub fn apply_all(&mut self, commands: &[PutObjCommand], player: &str, now: DateTime<UTC>) -> Result<Vec<(ObjectId, u64)>, (usize, StoreError)> {
+        self.undo = Some(UndoLog {
+            sequence: self.sequence,
+            delta_floor: self.delta_floor,
+            next_id: self.next_id,
+            ..UndoLog::default()
+        });
+
+        let mut versions = vec![];
+
+        for (index, command) in commands.iter().enumerate() {
+            match self.apply(command, player, now) {
+                Ok(version) => versions.push(version),
+                Err(error) => {
+                    self.rollback();
+
+                    return Err((index, error));
+                }
+            }
+        }
+
+        self.undo = None;
+
+        Ok(versions)
+    }
+
+    /**
+     * Remember the state of an object before a transaction changes it.
+     */
+    fn save_object(&mut self, id: ObjectId) {
+        let objects = &self.objects;
+
+        if let Some(undo) = &mut self.undo {
+            undo.objects.entry(id).or_insert_with(|| objects.get(&id).cloned());
+        }
+    }
+
+    fn save_tombstone(&mut self, id: ObjectId) {
+        let tombstones = &self.tombstones;
+
+        if let Some(undo) = &mut self.undo {
+            undo.tombstones.entry(id).or_insert_with(|| tombstones.get(&id).cloned());
+        }
+    }
+
+    /**
+     * Undo the changes of the transaction being applied.
+     */
+    fn rollback(&mut self) {
+        let undo = match self.undo.take() {
+            Some(undo) => undo,
+            None => return
+        };
+
+        for (id, saved) in undo.objects {
+            if let Some(current) = self.objects.remove(&id) {
+                self.grid.remove(id);
+                self.unindex(&current);
+                self.unlink_child(current.parent_id, id);
+            }
+
+            if let Some(object) = saved {
+                World::index_position(&mut self.grid, &object);
+                self.link_child(object.parent_id, id);
+                self.objects.insert(id, object);
+                self.reindex(id);
+            }
+        }
+
+        for (id, saved) in undo.tombstones {
+            match saved {
+                Some(tombstone) => self.tombstones.insert(id, tombstone),
+                None => self.tombstones.remove(&id)
+            };
+        }
+
+        self.sequence = undo.sequence;
+        self.delta_floor = undo.delta_floor;
+        self.next_id = undo.next_id;
+    }
+
+    /**
+     * Delete the objects that were not refreshed before the end of their
+     * ttl. Returns the ids of the deleted objects.
+     */
+    pub fn remove_expired(&mut self, now: DateTime<UTC>) -> Vec<ObjectId> {
+        let expired: Vec<ObjectId> = self.objects.values()
+                                         .filter(|o| o.expires_at.is_some_and(|t| t <= now))
+                                         .map(|o| o.id)
+                                         .collect();
+
+        // Expired objects may already be gone with an expired parent.
+        for id in &expired {
+            if self.objects.contains_key(id) {
+                self.delete(*id, 0).unwrap();
+            }
+        }
+
+        expired
+    }
+
+    /**
+     * Register an object class, or replace its schema. Objects already in
+     * the world are not checked again.
+     */
+    pub fn register_class(&mut self, schema: ClassSchema) {
+        let mut indexes = HashMap::new();
+
+        // Indexes are built again from the objects already in the world.
+        for property in schema.properties.iter().filter(|p| p.index != IndexKind::None) {
+            let mut index = PropertyIndex::new(property.index);
+
+            for object in self.objects.values().filter(|o| o.class == schema.name) {
+                let key = object.get_property(&property.property_id).and_then(|p| IndexKey::from_property(p, property.value_type));
+
+                index.insert(object.id, key);
+            }
+
+            indexes.insert(property.property_id.clone(), index);
+        }
+
+        self.indexes.insert(schema.name.clone(), indexes);
+        self.classes.insert(schema.name.clone(), schema);
+    }
+
+    /**
+     * Update the index entries of an object after a change.
+     */
+    fn reindex(&mut self, id: ObjectId) {
+        let object = &self.objects[&id];
+
+        let owner = Some(IndexKey::Str(object.owner.clone())).filter(|_| !object.owner.is_empty());
+        self.owner_index.insert(id, owner);
+
+        if let (Some(indexes), Some(schema)) = (self.indexes.get_mut(&object.class), self.classes.get(&object.class)) {
+            for (property_id, index) in indexes.iter_mut() {
+                let key = object.get_property(property_id).and_then(|p| IndexKey::from_property(p, schema.value_type(property_id)));
+
+                index.insert(id, key);
+            }
+        }
+    }
+
+    fn unindex(&mut self, object: &StoredObject) {
+        self.owner_index.remove(object.id);
+
+        if let Some(indexes) = self.indexes.get_mut(&object.class) {
+            for index in indexes.values_mut() {
+                index.remove(object.id);
+            }
+        }
+    }
+
+    /**
+     * Find the objects which may match a query with the indexes, narrowed by
+     * every criteria an index can serve. Returns None when no index can be
+     * used and every object must be checked.
+     */
+    fn index_candidates(&self, query: &QueryCommand) -> Option<BTreeSet<ObjectId>> {
+        let mut candidates = vec![];
+
+        if !query.owner.is_empty() {
+            candidates.extend(self.owner_index.lookup(Comparison::Equal, &IndexKey::Str(query.owner.clone())));
+        }
+
+        if let (Some(indexes), Some(schema)) = (self.indexes.get(&query.class_name), self.classes.get(&query.class_name)) {
+            for filter in &query.filters {
+                let property_id = schema.property_id(&filter.operand.name).unwrap_or(&filter.operand.name);
+
+                let key = IndexKey::from_property(&filter.operand, schema.value_type(property_id));
+
+                if let (Some(index), Some(key)) = (indexes.get(property_id), key) {
+                    candidates.extend(index.lookup(filter.comparison, &key));
+                }
+            }
+        }
+
+        candidates.into_iter().reduce(|a, b| a.intersection(&b).cloned().collect())
+    }
+
+    pub fn get_class(&self, name: &str) -> Option<&ClassSchema> {
+        self.classes.get(name)
+    }
+
+    /**
+     * Type of a property of an object, given by its class.
+     */
+    pub fn value_type(&self, object: &StoredObject, property_id: &str) -> ValueType {
+        self.classes.get(&object.class).map_or(ValueType::Raw, |s| s.value_type(property_id))
+    }
+
+    /**
+     * Properties of the objects of a class which are not public.
+     */
+    fn restricted_properties(&self, class: &str) -> Vec<(String, Visibility)> {
+        match self.classes.get(class) {
+            Some(schema) => schema.properties.iter()
+                                             .filter(|p| p.visibility != Visibility::Public)
+                                             .map(|p| (p.property_id.clone(), p.visibility))
+                                             .collect(),
+            None => vec![]
+        }
+    }
+
+    /**
+     * Checks if a viewer can see a property of an object, properties of
+     * objects without a class are public.
+     */
+    pub fn can_see(&self, object: &StoredObject, property_id: &str, viewer: &Viewer) -> bool {
+        self.classes.get(&object.class).is_none_or(|s| viewer.can_see(s.visibility(property_id), &object.owner))
+    }
+
+    /**
+     * Leave out of an entry of an object the properties a viewer cannot see.
+     */
+    pub fn visible_entry(&self, object: &StoredObject, mut entry: ObjectEntry, viewer: &Viewer) -> ObjectEntry {
+        entry.properties.retain(|p| self.can_see(object, &p.name, viewer));
+
+        entry
+    }
+
+    /**
+     * Translate property names requested by a client to the property ids of
+     * the class of the object. Names the class does not know are kept as is.
+     */
+    pub fn resolve_property_names(&self, object: &StoredObject, names: &[String]) -> Vec<String> {
+        match self.classes.get(&object.class) {
+            Some(schema) => names.iter().map(|n| String::from(schema.property_id(n).unwrap_or(n))).collect(),
+            None => names.to_vec()
+        }
+    }
+
+    fn validate_class(&self, class: &str, properties: &[ObjProperties], is_new: bool) -> Result<(), StoreError> {
+        if class.is_empty() {
+            return Ok(());
+        }
+
+        let schema = self.classes.get(class).ok_or(StoreError::SchemaViolation(format!("Class {} does not exist", class)))?;
+
+        schema.validate(properties, is_new).map_err(StoreError::SchemaViolation)
+    }
+
+    /**
+     * Give an object to another player. Owners are not part of the object
+     * state sent to clients so the version does not change.
+     */
+    pub fn set_owner(&mut self, id: ObjectId, owner: &str) -> Result<(), StoreError> {
+        self.save_object(id);
+
+        let object = self.objects.get_mut(&id).ok_or(StoreError::NotFound(id))?;
+        object.owner = String::from(owner);
+
+        self.reindex(id);
+
+        Ok(())
+    }
+
+    pub fn owned_by(&self, player: &str) -> Vec<&StoredObject> {
+        let ids = self.owner_index.lookup(Comparison::Equal, &IndexKey::Str(String::from(player))).unwrap_or_default();
+
+        ids.iter().filter_map(|id| self.objects.get(id)).collect()
+    }
+
+    /**
+     * List the players owning at least one object.
+     */
+    pub fn owners(&self) -> Vec<String> {
+        let mut owners: Vec<String> = self.objects.values()
+                                          .filter(|o| !o.owner.is_empty())
+                                          .map(|o| o.owner.clone())
+                                          .collect();
+        owners.sort();
+        owners.dedup();
+
+        owners
+    }
+
+    /**
+     * Register an object template, or replace it with its next version. The
+     * defaults are checked against the class of the template, required
+     * properties are only checked when spawning.
+     */
+    pub fn register_template(&mut self, name: &str, class: &str, properties: &[ObjProperties]) -> Result<&ObjectTemplate, StoreError> {
+        if name.is_empty() {
+            return Err(StoreError::InvalidValue(String::from("Template name cannot be empty")));
+        }
+
+        self.validate_class(class, properties, false)?;
+
+        let version = self.templates.get(name).map_or(1, |t| t.version + 1);
+
+        let template = ObjectTemplate {
+            name: String::from(name),
+            version,
+            class: String::from(class),
+            properties: properties.to_vec()
+        };
+
+        self.templates.insert(String::from(name), template);
+
+        Ok(&self.templates[name])
+    }
+
+    /**
+     * List the templates, sorted by name.
+     */
+    pub fn templates(&self) -> Vec<&ObjectTemplate> {
+        self.templates.values().collect()
+    }
+
+    /**
+     * Add an object from a template, see SPAWN. Returns the id and the
+     * version of the new object.
+     */
+    pub fn spawn(&mut self, command: &SpawnCommand, player: &str, now: DateTime<UTC>) -> Result<(ObjectId, u64), StoreError> {
+        let template = self.templates.get(&command.template).ok_or_else(|| StoreError::TemplateNotFound(command.template.clone()))?;
+
+        if command.template_version != 0 && command.template_version != template.version {
+            return Err(StoreError::StaleTemplate {
+                name: template.name.clone(),
+                expected: command.template_version,
+                current: template.version
+            });
+        }
+
+        let mut add = PutObjCommand::from_info(PutOperation::Add, command.object_id, &command.object_name, template.properties_with(&command.properties));
+        add.class_name = template.class.clone();
+        add.on_owner_leave = command.on_owner_leave;
+        add.ttl = command.ttl;
+        add.parent_id = command.parent_id;
+
+        self.apply(&add, player, now)
+    }
+
+    /**
+     * Reserve a block of ids from SERVER_ID_START on, the server never hands
+     * out an id twice. Returns the first id of the block.
+     */
+    fn allocate_ids(&mut self, count: u64) -> Result<ObjectId, StoreError> {
+        let start = self.next_id.max(SERVER_ID_START);
+        self.next_id = start.checked_add(count).ok_or(StoreError::IdsExhausted)?;
+
+        Ok(start)
+    }
+
+    /**
+     * Lease a block of ids to a player, only this player can add objects
+     * with them until the lease is released. Returns the first id.
+     */
+    pub fn lease_ids(&mut self, player: &str, count: u64) -> Result<ObjectId, StoreError> {
+        if self.leases.values().filter(|(_, holder)| holder == player).count() >= MAX_LEASES {
+            return Err(StoreError::TooManyLeases(String::from(player)));
+        }
+
+        let start = self.allocate_ids(count)?;
+
+        self.leases.insert(start, (start + count, String::from(player)));
+
+        Ok(start)
+    }
+
+    /**
+     * Release the ids leased by a player. Ids it did not use are not handed
+     * out again.
+     */
+    pub fn release_leases(&mut self, player: &str) {
+        self.leases.retain(|_, (_, holder)| holder != player);
+    }
+
+    pub fn lease_holder(&self, id: ObjectId) -> Option<&str> {
+        self.leases.range(..=id)
+                   .next_back()
+                   .filter(|(_, (end, _))| id < *end)
+                   .map(|(_, (_, holder))| holder.as_str())
+    }
+
+    /**
+     * List the players holding leased ids.
+     */
+    pub fn lease_holders(&self) -> Vec<String> {
+        let holders: BTreeSet<&String> = self.leases.values().map(|(_, holder)| holder).collect();
+
+        holders.into_iter().cloned().collect()
+    }
+
+    /**
+     * Set how long past states of the objects are kept. Only the changes
+     * applied from now on are recorded.
+     */
+    pub fn set_history_duration(&mut self, history_ms: u64) {
+        self.history_ms = history_ms;
+    }
+
+    /**
+     * Find the state of an object at a past time, see StoredObject::state_at.
+     * Properties the viewer cannot see are left out.
+     */
+    pub fn state_at(&self, id: ObjectId, at: DateTime<UTC>, mode: RewindMode, property_names: &[String], viewer: &Viewer) -> Result<ObjectEntry, StoreError> {
+        let object = self.objects.get(&id).ok_or(StoreError::NotFound(id))?;
+        let property_names = self.resolve_property_names(object, property_names);
+
+        object.state_at(at, mode, &property_names, self.classes.get(&object.class))
+              .map(|entry| self.visible_entry(object, entry, viewer))
+              .ok_or(StoreError::NoHistory(id))
+    }
+
+    /**
+     * Find the objects matching a query, see QUERY. The property names of
+     * the query are resolved against the class of each object.
+     *
+     * Properties the viewer cannot see are treated as missing, so filters and
+     * sorting on them do not reveal their values.
+     */
+    pub fn query(&self, query: &QueryCommand, viewer: &Viewer) -> Vec<ObjectEntry> {
+        let property_value = |object: &StoredObject, name: &str| {
+            let id = self.classes.get(&object.class).and_then(|s| s.property_id(name)).unwrap_or(name);
+
+            if !self.can_see(object, id, viewer) {
+                return None;
+            }
+
+            object.get_property(id).and_then(|p| p.value_as(self.value_type(object, id)).ok())
+        };
+
+        let objects: Vec<&StoredObject> = match self.index_candidates(query) {
+            Some(ids) => ids.iter().filter_map(|id| self.objects.get(id)).collect(),
+            None => self.objects.values().collect()
+        };
+
+        let mut found: Vec<(Option<PropertyValue>, &StoredObject)> = objects.into_iter()
+            .filter(|o| glob_matches(&query.name_pattern, &o.name))
+            .filter(|o| query.class_name.is_empty() || o.class == query.class_name)
+            .filter(|o| query.owner.is_empty() || o.owner == query.owner)
+            .filter(|o| query.filters.iter().all(|f| f.matches(property_value(o, &f.operand.name).as_ref())))
+            .map(|o| (property_value(o, &query.sort_property), o))
+            .collect();
+
+        // The sort is stable so objects with the same value stay sorted by id.
+        if !query.sort_property.is_empty() {
+            found.sort_by(|(a, _), (b, _)| sort_order(a, b, query.descending));
+        }
+
+        if query.limit > 0 {
+            found.truncate(query.limit as usize);
+        }
+
+        found.into_iter()
+             .map(|(_, o)| self.visible_entry(o, o.to_entry(&self.resolve_property_names(o, &query.property_names)), viewer))
+             .collect()
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&StoredObject> {
+        self.objects.get(&id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&StoredObject> {
+        self.objects.values().find(|o| o.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+}
+
+/**
+ * Holds the objects of every game session, each session has its own world so
+ * object ids only need to be unique inside a session.
+ */
+pub struct ObjectStore {
+    worlds: HashMap<String, World>
+}
+
+impl ObjectStore {
+    pub fn new() -> ObjectStore {
+        ObjectStore {
+            worlds: HashMap::new()
+        }
+    }
+
+    pub fn world(&self, game: &str) -> Option<&World> {
+        self.worlds.get(game)
+    }
+
+    /**
+     * Get the world of a game session, creating it on first use.
+     */
+    pub fn world_mut(&mut self, game: &str) -> &mut World {
+        self.worlds.entry(String::from(game)).or_default()
+    }
+
+    pub fn world_names(&self) -> Vec<String> {
+        self.worlds.keys().cloned().collect()
+    }
+
+    pub fn remove_world(&mut self, game: &str) {
+        self.worlds.remove(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{ClassCommand, PropertySchema, ValueType, QueryFilter};
+
+    fn prop(name: &str, data: &[u8]) -> ObjProperties {
+        ObjProperties::raw(name, data)
+    }
+
+    /**
+     * Register a class declaring public properties of the given types.
+     */
+    fn declare_class(world: &mut World, class: &str, properties: &[(&str, ValueType)]) {
+        let properties = properties.iter().map(|(id, value_type)| PropertySchema {
+            property_id: String::from(*id),
+            name: String::new(),
+            value_type: *value_type,
+            min_size: 0,
+            max_size: 0,
+            required: false,
+            index: IndexKind::None,
+            visibility: Visibility::Public
+        }).collect();
+
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info(class, properties)).unwrap());
+    }
+
+    #[test]
+    fn test_add_update_delete() {
+        let mut world = World::default();
+
+        world.add(1, "Ship1", &[prop("Position", b"1,1"), prop("Health", b"100")]).unwrap();
+        assert_eq!(world.add(1, "Other", &[]), Err(StoreError::AlreadyExists(1)));
+        assert_eq!(world.add(2, "Ship1", &[]), Err(StoreError::NameTaken(String::from("Ship1"))));
+
+        // Update replaces the given properties only.
+        let updated = world.update(1, &[prop("Position", b"2,2"), prop("Cargo", b"Rum")], 0).unwrap();
+        assert_eq!(updated.get_property("Position").unwrap().data, b"2,2");
+        assert_eq!(updated.get_property("Health").unwrap().data, b"100");
+        assert_eq!(updated.properties.len(), 3);
+
+        assert_eq!(world.update(2, &[], 0), Err(StoreError::NotFound(2)));
+
+        world.delete(1, 0).unwrap();
+        assert_eq!(world.delete(1, 0), Err(StoreError::NotFound(1)));
+        assert_eq!(world.len(), 0);
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut world = World::default();
+
+        let added = world.add(1, "Ship1", &[prop("Position", b"1,1"), prop("Health", b"100")]).unwrap();
+        assert_eq!(added.version, 1);
+
+        let updated = world.update(1, &[prop("Position", b"2,2")], 1).unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.property_version("Position"), Some(2));
+        assert_eq!(updated.property_version("Health"), Some(1));
+
+        // A writer that did not see version 2 is rejected.
+        let conflict = world.update(1, &[prop("Position", b"3,3")], 1);
+        assert_eq!(conflict, Err(StoreError::VersionConflict { id: 1, expected: 1, current: 2 }));
+        assert_eq!(world.get(1).unwrap().get_property("Position").unwrap().data, b"2,2");
+
+        world.delete(1, 1).expect_err("Delete is stale");
+        world.delete(1, 2).unwrap();
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut world = World::default();
+
+        world.add(1, "Ship1", &[prop("Position", b"1,1"), prop("Health", b"100")]).unwrap();
+        world.add(2, "Ship2", &[prop("Position", b"5,5")]).unwrap();
+        let synced = world.sequence();
+
+        world.update(1, &[prop("Position", b"2,2")], 0).unwrap();
+        world.delete(2, 0).unwrap();
+
+        let (changes, reset) = world.changes_since(synced, None, &Viewer::everything());
+        assert!(!reset);
+        assert_eq!(changes.len(), 2);
+
+        // Only the property that changed is sent.
+        assert_eq!(changes[0].object_id, 1);
+        assert_eq!(changes[0].properties, vec![prop("Position", b"2,2")]);
+
+        // Deleted objects are sent as tombstones.
+        assert_eq!(changes[1].object_id, 2);
+        assert_eq!(changes[1].status, EntryStatus::Deleted);
+
+        // Nothing changed since the last change.
+        assert!(world.changes_since(world.sequence(), None, &Viewer::everything()).0.is_empty());
+
+        // Syncing from the start returns every property of live objects.
+        let (changes, _) = world.changes_since(0, None, &Viewer::everything());
+        assert_eq!(changes[0].properties.len(), 2);
+    }
+
+    #[test]
+    fn test_changes_after_tombstones_forgotten() {
+        let mut world = World::default();
+
+        for id in 0..(MAX_TOMBSTONES as u64 + 1) {
+            world.add(id, "", &[]).unwrap();
+            world.delete(id, 0).unwrap();
+        }
+        world.add(9999, "Ship", &[]).unwrap();
+
+        // The first delete was forgotten so a full state is sent.
+        let (changes, reset) = world.changes_since(0, None, &Viewer::everything());
+        assert!(reset);
+        assert_eq!(changes.len(), 1);
+
+        let (_, reset) = world.changes_since(world.sequence() - 1, None, &Viewer::everything());
+        assert!(!reset);
+    }
+
+    fn position(x: f64, y: f64) -> ObjProperties {
+        ObjProperties::typed(POSITION_PROPERTY, &PropertyValue::Vec2(x, y))
+    }
+
+    #[test]
+    fn test_spatial_index() {
+        let mut world = World::default();
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+
+        world.add(1, "Ship1", &[position(10.0, 10.0)]).unwrap();
+        world.add(2, "Ship2", &[position(500.0, 10.0)]).unwrap();
+        world.add(3, "Flag", &[]).unwrap();
+
+        let inside: Vec<ObjectId> = world.objects_in(&area, &Viewer::everything()).iter().map(|o| o.id).collect();
+        assert_eq!(inside, vec![1]);
+
+        world.update(2, &[position(50.0, 50.0)], 0).unwrap();
+        world.delete(1, 0).unwrap();
+
+        let inside: Vec<ObjectId> = world.objects_in(&area, &Viewer::everything()).iter().map(|o| o.id).collect();
+        assert_eq!(inside, vec![2]);
+    }
+
+    #[test]
+    fn test_change_enters_and_leaves_area() {
+        let mut world = World::default();
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+
+        world.add(1, "Ship1", &[position(500.0, 10.0), prop("Health", b"100")]).unwrap();
+
+        // Created outside the area, not sent.
+        let changes = world.change_set(0);
+        assert_eq!(changes[0].entry_for(Some(&area), &Viewer::everything()), None);
+        assert!(changes[0].entry_for(None, &Viewer::everything()).is_some());
+
+        // Moving inside sends every property.
+        let before = world.sequence();
+        world.update(1, &[position(50.0, 10.0)], 0).unwrap();
+        let entry = world.change_set(before)[0].entry_for(Some(&area), &Viewer::everything()).unwrap();
+        assert_eq!(entry.status, EntryStatus::Entered);
+        assert_eq!(entry.properties.len(), 2);
+
+        // Moving inside the area only sends the change.
+        let before = world.sequence();
+        world.update(1, &[position(60.0, 10.0)], 0).unwrap();
+        let entry = world.change_set(before)[0].entry_for(Some(&area), &Viewer::everything()).unwrap();
+        assert_eq!(entry.status, EntryStatus::Found);
+        assert_eq!(entry.properties.len(), 1);
+
+        // Moving out sends a Left entry.
+        let before = world.sequence();
+        world.update(1, &[position(600.0, 10.0)], 0).unwrap();
+        let entry = world.change_set(before)[0].entry_for(Some(&area), &Viewer::everything()).unwrap();
+        assert_eq!(entry.status, EntryStatus::Left);
+        assert!(entry.properties.is_empty());
+
+        // Deleted outside the area, not sent.
+        let before = world.sequence();
+        world.delete(1, 0).unwrap();
+        assert_eq!(world.change_set(before)[0].entry_for(Some(&area), &Viewer::everything()), None);
+    }
+
+    #[test]
+    fn test_changes_since_in_area() {
+        let mut world = World::default();
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+
+        world.add(1, "Ship1", &[position(10.0, 10.0), prop("Health", b"100")]).unwrap();
+        world.add(2, "Ship2", &[position(500.0, 10.0), prop("Health", b"100")]).unwrap();
+        world.add(3, "Flag", &[]).unwrap();
+        let synced = world.sequence();
+
+        let (changes, _) = world.changes_since(0, Some(&area), &Viewer::everything());
+        let ids: Vec<u64> = changes.iter().map(|e| e.object_id).collect();
+        assert_eq!(ids, vec![1, 3]);
+
+        world.update(1, &[position(700.0, 10.0)], 0).unwrap();
+        world.update(2, &[position(20.0, 10.0)], 0).unwrap();
+
+        let (changes, _) = world.changes_since(synced, Some(&area), &Viewer::everything());
+        assert_eq!(changes[0].status, EntryStatus::Left);
+        assert_eq!(changes[1].status, EntryStatus::Entered);
+        assert_eq!(changes[1].properties.len(), 2);
+
+        // Objects that stay outside the area are not sent, objects moving
+        // inside it only send the change.
+        let synced = world.sequence();
+        world.update(1, &[prop("Health", b"90")], 0).unwrap();
+        world.update(1, &[position(800.0, 10.0)], 0).unwrap();
+        world.update(2, &[position(30.0, 10.0)], 0).unwrap();
+
+        let (changes, _) = world.changes_since(synced, Some(&area), &Viewer::everything());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].status, EntryStatus::Found);
+        assert_eq!(changes[0].properties.len(), 1);
+
+        // The position at the last sync is lost after two moves, the object
+        // may have been seen.
+        let synced = world.sequence();
+        world.update(1, &[position(900.0, 10.0)], 0).unwrap();
+        world.update(1, &[position(950.0, 10.0)], 0).unwrap();
+        assert_eq!(world.changes_since(synced, Some(&area), &Viewer::everything()).0[0].status, EntryStatus::Left);
+    }
+
+    #[test]
+    fn test_add_after_delete_keeps_version() {
+        let mut world = World::default();
+
+        world.add(1, "Ship1", &[]).unwrap();
+        world.delete(1, 0).unwrap();
+
+        assert_eq!(world.add(1, "Ship1", &[]).unwrap().version, 3);
+    }
+
+    #[test]
+    fn test_apply_command() {
+        let mut world = World::default();
+
+        let add = PutObjCommand::from_info(PutOperation::Add, 3, "Island3", vec![prop("Size", b"50")]);
+        world.apply(&add, "Blackbeard", UTC::now()).unwrap();
+        assert_eq!(world.get(3).unwrap().owner, "Blackbeard");
+        assert_eq!(world.find_by_name("Island3").unwrap().id, 3);
+
+        let update = PutObjCommand::from_info(PutOperation::Update, 3, "", vec![prop("Size", b"60")]);
+        assert_eq!(world.apply(&update, "Blackbeard", UTC::now()), Ok((3, 2)));
+
+        let delete = PutObjCommand::from_info(PutOperation::Delete, 3, "", vec![]);
+        assert_eq!(world.apply(&delete, "Blackbeard", UTC::now()), Ok((3, 3)));
+        assert!(world.get(3).is_none());
+    }
+
+    #[test]
+    fn test_to_entry() {
+        let mut world = World::default();
+        let object = world.add(1, "Ship1", &[prop("Position", b"1,1"), prop("Health", b"100")]).unwrap();
+
+        assert_eq!(object.to_entry(&[]).properties.len(), 2);
+
+        let entry = object.to_entry(&[String::from("Health")]);
+        assert_eq!(entry.status, EntryStatus::Found);
+        assert_eq!(entry.properties, vec![prop("Health", b"100")]);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let mut world = World::default();
+        declare_class(&mut world, "Ship", &[("Health", ValueType::Int)]);
+
+        // The type of the data comes from the class.
+        let health = ObjProperties::typed("Health", &PropertyValue::Int(100));
+        world.add_with_class(1, "Ship1", "Ship", &[health]).unwrap();
+        let object = world.get(1).unwrap();
+        assert_eq!(world.value_type(object, "Health"), ValueType::Int);
+        assert_eq!(world.value_type(object, "Cargo"), ValueType::Raw);
+
+        // Data that is not a value of the type is rejected.
+        let mut broken = ObjProperties::typed("Health", &PropertyValue::Int(50));
+        broken.data.pop();
+        assert!(matches!(world.update(1, &[broken], 0), Err(StoreError::SchemaViolation(_))));
+        assert_eq!(world.get(1).unwrap().version, 1);
+
+        // Untyped positions are still placed in the spatial index.
+        let mut data = crate::utils::f64_to_buf(4.0);
+        data.append(&mut crate::utils::f64_to_buf(8.0));
+        world.add(2, "Ship2", &[prop(POSITION_PROPERTY, &data)]).unwrap();
+        assert_eq!(world.get(2).unwrap().position(), Some((4.0, 8.0)));
+    }
+
+    #[test]
+    fn test_class_schema() {
+        let mut world = World::default();
+
+        let speed = PropertySchema {
+            property_id: String::from("Spd"),
+            name: String::from("Speed"),
+            value_type: ValueType::Float,
+            min_size: 0,
+            max_size: 0,
+            required: true,
+            index: IndexKind::None,
+            visibility: Visibility::Public
+        };
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![speed])).unwrap());
+
+        let fast = ObjProperties::typed("Spd", &PropertyValue::Float(12.0));
+        assert!(matches!(world.add_with_class(1, "Ship1", "Ship", &[]), Err(StoreError::SchemaViolation(_))));
+        assert!(matches!(world.add_with_class(1, "Ship1", "Island", std::slice::from_ref(&fast)), Err(StoreError::SchemaViolation(_))));
+
+        world.add_with_class(1, "Ship1", "Ship", &[fast]).unwrap();
+        assert!(matches!(world.update(1, &[prop("Spd", b"fast")], 0), Err(StoreError::SchemaViolation(_))));
+
+        // Rejected writes do not reach the store.
+        assert_eq!(world.get(1).unwrap().version, 1);
+
+        let object = world.get(1).unwrap();
+        let names = world.resolve_property_names(object, &[String::from("Speed"), String::from("Other")]);
+        assert_eq!(names, vec![String::from("Spd"), String::from("Other")]);
+    }
+
+    #[test]
+    fn test_ownership() {
+        let mut world = World::default();
+
+        let mut add = PutObjCommand::from_info(PutOperation::Add, 1, "Ship1", vec![]);
+        add.on_owner_leave = OwnerLeavePolicy::Delete;
+        world.apply(&add, "Blackbeard", UTC::now()).unwrap();
+        world.add(2, "Island", &[]).unwrap();
+
+        let owned: Vec<ObjectId> = world.owned_by("Blackbeard").iter().map(|o| o.id).collect();
+        assert_eq!(owned, vec![1]);
+        assert_eq!(world.get(1).unwrap().on_owner_leave, OwnerLeavePolicy::Delete);
+
+        world.set_owner(1, "Calico Jack").unwrap();
+        assert!(world.owned_by("Blackbeard").is_empty());
+        assert_eq!(world.get(1).unwrap().version, 1);
+
+        assert_eq!(world.set_owner(3, "Calico Jack"), Err(StoreError::NotFound(3)));
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let mut world = World::default();
+        let now = UTC::now();
+
+        let mut add = PutObjCommand::from_info(PutOperation::Add, 1, "Ghost", vec![]);
+        add.ttl = 10;
+        world.apply(&add, "Blackbeard", now).unwrap();
+        world.add(2, "Island", &[]).unwrap();
+
+        assert!(world.remove_expired(now + chrono::Duration::seconds(5)).is_empty());
+
+        // An update refreshes the object.
+        let refresh = PutObjCommand::from_info(PutOperation::Update, 1, "", vec![]);
+        world.apply(&refresh, "Blackbeard", now + chrono::Duration::seconds(5)).unwrap();
+        assert!(world.remove_expired(now + chrono::Duration::seconds(12)).is_empty());
+
+        let before = world.sequence();
+        assert_eq!(world.remove_expired(now + chrono::Duration::seconds(15)), vec![1]);
+        assert!(world.get(1).is_none());
+        assert_eq!(world.change_set(before)[0].entry.status, EntryStatus::Deleted);
+        assert_eq!(world.owners(), Vec::<String>::new());
+
+        add.ttl = u64::MAX;
+        assert!(matches!(world.apply(&add, "Blackbeard", now), Err(StoreError::InvalidValue(_))));
+    }
+
+    #[test]
+    fn test_apply_all() {
+        let mut world = World::default();
+        world.add(1, "Ship1", &[prop("Cargo", b"100")]).unwrap();
+        world.add(2, "Ship2", &[prop("Cargo", b"0")]).unwrap();
+        let before = world.sequence();
+
+        let mut give = PutObjCommand::from_info(PutOperation::Update, 1, "", vec![prop("Cargo", b"0")]);
+        give.expected_version = 1;
+        let mut take = PutObjCommand::from_info(PutOperation::Update, 2, "", vec![prop("Cargo", b"100")]);
+        take.expected_version = 5;
+
+        // The second version check fails so the first update is not applied.
+        let result = world.apply_all(&[give, take], "Blackbeard", UTC::now());
+        assert_eq!(result, Err((1, StoreError::VersionConflict { id: 2, expected: 5, current: 1 })));
+        assert_eq!(world.get(1).unwrap().get_property("Cargo").unwrap().data, b"100");
+        assert_eq!(world.sequence(), before);
+
+        let give = PutObjCommand::from_info(PutOperation::Update, 1, "", vec![prop("Cargo", b"0")]);
+        let take = PutObjCommand::from_info(PutOperation::Update, 2, "", vec![prop("Cargo", b"100")]);
+        let add = PutObjCommand::from_info(PutOperation::Add, 3, "Receipt", vec![]);
+
+        assert_eq!(world.apply_all(&[give, take, add], "Blackbeard", UTC::now()), Ok(vec![(1, 2), (2, 2), (3, 1)]));
+        assert_eq!(world.change_set(before).len(), 3);
+
+        // Added and deleted objects are put back with their indexes.
+        let before = world.sequence();
+        let add = PutObjCommand::from_info(PutOperation::Add, 4, "Wreck", vec![position(10.0, 10.0)]);
+        let delete = PutObjCommand::from_info(PutOperation::Delete, 3, "", vec![]);
+        let missing = PutObjCommand::from_info(PutOperation::Update, 9, "", vec![]);
+
+        let result = world.apply_all(&[add, delete, missing], "Blackbeard", UTC::now());
+        assert_eq!(result, Err((2, StoreError::NotFound(9))));
+        assert!(world.get(4).is_none());
+        assert_eq!(world.get(3).unwrap().version, 1);
+        assert!(world.objects_in(&Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 }, &Viewer::everything()).is_empty());
+        assert_eq!(world.owned_by("Blackbeard").len(), 1);
+        assert_eq!(world.sequence(), before);
+        assert!(world.change_set(before).is_empty());
+    }
+
+    #[test]
+    fn test_atomic_operations() {
+        let int = |name: &str, value: i64| ObjProperties::typed(name, &PropertyValue::Int(value));
+        let op = |operation: PutOperation, props: Vec<ObjProperties>| PutObjCommand::from_info(operation, 1, "", props);
+
+        let mut world = World::default();
+        declare_class(&mut world, "Island", &[("Gold", ValueType::Int), ("People", ValueType::Int), ("Cargo", ValueType::List), ("Flag", ValueType::Int)]);
+        world.add_with_class(1, "Island", "Island", &[int("Gold", 10)]).unwrap();
+
+        // Bounded decrement, the second one would go below 0.
+        let result = world.apply_atomic(&op(PutOperation::Decrement, vec![int("Gold", 6), int("", 0)]), UTC::now()).unwrap();
+        assert_eq!(result, AtomicResult { version: 2, applied: true, value: Some(int("Gold", 4)) });
+
+        let result = world.apply_atomic(&op(PutOperation::Decrement, vec![int("Gold", 6), int("", 0)]), UTC::now()).unwrap();
+        assert_eq!(result, AtomicResult { version: 2, applied: false, value: Some(int("Gold", 4)) });
+
+        // A missing counter starts at 0.
+        let result = world.apply_atomic(&op(PutOperation::Increment, vec![int("People", 3)]), UTC::now()).unwrap();
+        assert_eq!(result.value, Some(int("People", 3)));
+
+        world.apply_atomic(&op(PutOperation::Increment, vec![prop("Gold", b"1")]), UTC::now()).expect_err("Gold is an 8 bytes Int");
+
+        let result = world.apply_atomic(&op(PutOperation::CompareAndSwap, vec![int("Gold", 5), int("", 0)]), UTC::now()).unwrap();
+        assert!(!result.applied);
+        let result = world.apply_atomic(&op(PutOperation::CompareAndSwap, vec![int("Gold", 4), int("", 0)]), UTC::now()).unwrap();
+        assert_eq!(result.value, Some(int("Gold", 0)));
+
+        // List operands are items with their own type.
+        let item = |value: &PropertyValue| prop("Cargo", &value.encode_item());
+        let rum = PropertyValue::Str(String::from("Rum"));
+        world.apply_atomic(&op(PutOperation::Append, vec![item(&rum)]), UTC::now()).unwrap();
+        let result = world.apply_atomic(&op(PutOperation::Append, vec![item(&PropertyValue::Int(2))]), UTC::now()).unwrap();
+        assert_eq!(result.value, Some(ObjProperties::typed("Cargo", &PropertyValue::List(vec![rum.clone(), PropertyValue::Int(2)]))));
+
+        let result = world.apply_atomic(&op(PutOperation::Remove, vec![item(&rum)]), UTC::now()).unwrap();
+        assert_eq!(result.value, Some(ObjProperties::typed("Cargo", &PropertyValue::List(vec![PropertyValue::Int(2)]))));
+        assert!(!world.apply_atomic(&op(PutOperation::Remove, vec![item(&rum)]), UTC::now()).unwrap().applied);
+
+        assert!(!world.apply_atomic(&op(PutOperation::SetIfAbsent, vec![int("Gold", 100)]), UTC::now()).unwrap().applied);
+        assert!(world.apply_atomic(&op(PutOperation::SetIfAbsent, vec![int("Flag", 1)]), UTC::now()).unwrap().applied);
+
+        // Inside a transaction an operation that is not applied is an error.
+        let result = world.apply_all(&[op(PutOperation::Decrement, vec![int("Gold", 1), int("", 0)])], "Blackbeard", UTC::now());
+        assert_eq!(result, Err((0, StoreError::NotApplied { id: 1, property: String::from("Gold") })));
+    }
+
+    #[test]
+    fn test_query() {
+        let health = |value: i64| ObjProperties::typed("Health", &PropertyValue::Int(value));
+
+        let mut world = World::default();
+        declare_class(&mut world, "Ship", &[("Health", ValueType::Int)]);
+        declare_class(&mut world, "Island", &[("Health", ValueType::Int)]);
+
+        world.add_with_class(1, "Ship1", "Ship", &[health(50)]).unwrap();
+        world.add_with_class(2, "Ship2", "Ship", &[health(10)]).unwrap();
+        world.add_with_class(3, "Ship3", "Ship", &[health(15)]).unwrap();
+        world.add_with_class(4, "Ship4", "Ship", &[]).unwrap();
+        world.add_with_class(5, "Island1", "Island", &[health(5)]).unwrap();
+        world.set_owner(1, "Blackbeard").unwrap();
+        world.set_owner(2, "Blackbeard").unwrap();
+        world.set_owner(3, "Anne Bonny").unwrap();
+
+        let names = |entries: Vec<ObjectEntry>| entries.into_iter().map(|e| e.name).collect::<Vec<String>>();
+
+        let mut query = QueryCommand::from_info(vec![QueryFilter::new(Comparison::Less, health(20))]);
+        query.name_pattern = String::from("Ship*");
+        assert_eq!(names(world.query(&query, &Viewer::everything())), vec!["Ship2", "Ship3"]);
+
+        query.owner = String::from("Blackbeard");
+        assert_eq!(names(world.query(&query, &Viewer::everything())), vec!["Ship2"]);
+
+        // Objects without the sort property come last.
+        let mut query = QueryCommand::from_info(vec![]);
+        query.sort_property = String::from("Health");
+        query.descending = true;
+        assert_eq!(names(world.query(&query, &Viewer::everything())), vec!["Ship1", "Ship3", "Ship2", "Island1", "Ship4"]);
+
+        query.limit = 2;
+        query.property_names = vec![String::from("Cargo")];
+        let entries = world.query(&query, &Viewer::everything());
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].properties.is_empty());
+    }
+
+    #[test]
+    fn test_indexes() {
+        let schema = |id: &str, value_type: ValueType, index: IndexKind| PropertySchema {
+            property_id: String::from(id),
+            name: String::new(),
+            value_type,
+            min_size: 0,
+            max_size: 0,
+            required: false,
+            index,
+            visibility: Visibility::Public
+        };
+        let faction = |name: &str| ObjProperties::typed("Faction", &PropertyValue::Str(String::from(name)));
+        let health = |value: i64| ObjProperties::typed("Health", &PropertyValue::Int(value));
+
+        let mut world = World::default();
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![
+            schema("Faction", ValueType::Str, IndexKind::Hash),
+            schema("Health", ValueType::Int, IndexKind::None)
+        ])).unwrap());
+
+        world.add_with_class(1, "Ship1", "Ship", &[faction("Pirates"), health(50)]).unwrap();
+        world.add_with_class(2, "Ship2", "Ship", &[faction("Navy"), health(10)]).unwrap();
+        world.add_with_class(3, "Ship3", "Ship", &[faction("Pirates"), health(15)]).unwrap();
+        world.add(4, "Island", &[faction("Pirates")]).unwrap();
+
+        let mut pirates = QueryCommand::from_info(vec![QueryFilter::new(Comparison::Equal, faction("Pirates"))]);
+        pirates.class_name = String::from("Ship");
+        assert_eq!(world.index_candidates(&pirates), Some(vec![1, 3].into_iter().collect()));
+
+        // Updates and deletes, cascading ones included, keep the index in sync.
+        world.update(1, &[faction("Navy")], 0).unwrap();
+        world.reparent(3, 2, 0).unwrap();
+        world.delete(2, 0).unwrap();
+        world.add_with_class(5, "Ship5", "Ship", &[faction("Pirates"), health(5)]).unwrap();
+        assert_eq!(world.index_candidates(&pirates), Some(vec![5].into_iter().collect()));
+
+        // Declaring an index indexes the objects already in the world.
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![
+            schema("Faction", ValueType::Str, IndexKind::Hash),
+            schema("Health", ValueType::Int, IndexKind::Ordered)
+        ])).unwrap());
+
+        let mut weak = QueryCommand::from_info(vec![QueryFilter::new(Comparison::Less, health(20))]);
+        weak.class_name = String::from("Ship");
+        assert_eq!(world.index_candidates(&weak), Some(vec![5].into_iter().collect()));
+        assert_eq!(world.query(&weak, &Viewer::everything()).len(), 1);
+        assert_eq!(world.indexes["Ship"]["Health"].key_of(1), IndexKey::from_property(&health(50), ValueType::Int).as_ref());
+
+        // Transactions that fail leave the indexes untouched.
+        let add = PutObjCommand::from_info(PutOperation::Add, 6, "Ship6", vec![health(1)]);
+        let fail = PutObjCommand::from_info(PutOperation::Update, 42, "", vec![]);
+        world.apply_all(&[add, fail], "Blackbeard", UTC::now()).unwrap_err();
+        assert!(world.owned_by("Blackbeard").is_empty());
+
+        world.set_owner(5, "Blackbeard").unwrap();
+        assert_eq!(world.owned_by("Blackbeard").len(), 1);
+        world.delete(5, 0).unwrap();
+        assert!(world.owned_by("Blackbeard").is_empty());
+    }
+
+    #[test]
+    fn test_id_allocation() {
+        let add = |id: ObjectId| PutObjCommand::from_info(PutOperation::Add, id, "", vec![]);
+
+        let mut world = World::default();
+        world.apply(&add(7), "Blackbeard", UTC::now()).unwrap();
+
+        // Server ids are apart from the ids chosen by clients.
+        assert_eq!(world.apply(&add(0), "Blackbeard", UTC::now()), Ok((SERVER_ID_START, 1)));
+
+        let start = world.lease_ids("Anne Bonny", 10).unwrap();
+        assert_eq!(start, SERVER_ID_START + 1);
+        assert_eq!(world.lease_holder(start + 3), Some("Anne Bonny"));
+        assert_eq!(world.lease_holder(start + 10), None);
+        assert_eq!(world.apply(&add(0), "Blackbeard", UTC::now()), Ok((start + 10, 1)));
+
+        // Leased ids are reserved for the player holding them.
+        assert_eq!(world.apply(&add(start + 3), "Blackbeard", UTC::now()), Err(StoreError::IdLeased { id: start + 3, player: String::from("Anne Bonny") }));
+        world.apply(&add(start + 3), "Anne Bonny", UTC::now()).unwrap();
+
+        // Clients cannot pick server ids they did not lease.
+        assert_eq!(world.apply(&add(start + 100), "Anne Bonny", UTC::now()), Err(StoreError::IdReserved(start + 100)));
+        assert_eq!(world.apply(&add(ObjectId::MAX), "Blackbeard", UTC::now()), Err(StoreError::IdReserved(ObjectId::MAX)));
+
+        // Released ids are not handed out again.
+        world.release_leases("Anne Bonny");
+        assert!(world.lease_holders().is_empty());
+        assert_eq!(world.apply(&add(start + 4), "Anne Bonny", UTC::now()), Err(StoreError::IdReserved(start + 4)));
+        assert_eq!(world.apply(&add(0), "Blackbeard", UTC::now()), Ok((start + 11, 1)));
+
+        // Players hold a bounded number of leases.
+        for _ in 0..MAX_LEASES {
+            world.lease_ids("Anne Bonny", 1).unwrap();
+        }
+
+        assert_eq!(world.lease_ids("Anne Bonny", 1), Err(StoreError::TooManyLeases(String::from("Anne Bonny"))));
+        assert_eq!(world.lease_holders(), vec![String::from("Anne Bonny")]);
+        world.lease_ids("Calico Jack", 1).unwrap();
+
+        world.next_id = ObjectId::MAX;
+        assert_eq!(world.apply(&add(0), "Blackbeard", UTC::now()), Err(StoreError::IdsExhausted));
+    }
+
+    #[test]
+    fn test_templates() {
+        let cannons = |count: i64| ObjProperties::typed("Cannons", &PropertyValue::Int(count));
+
+        let mut world = World::default();
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![PropertySchema {
+            property_id: String::from("Cannons"),
+            name: String::new(),
+            value_type: ValueType::Int,
+            min_size: 0,
+            max_size: 0,
+            required: true,
+            index: IndexKind::None,
+            visibility: Visibility::Public
+        }])).unwrap());
+
+        assert!(matches!(world.register_template("Sloop", "Ship", &[prop("Cannons", b"8")]), Err(StoreError::SchemaViolation(_))));
+        assert_eq!(world.register_template("Sloop", "Ship", &[cannons(8)]).unwrap().version, 1);
+        assert_eq!(world.register_template("Sloop", "Ship", &[cannons(10)]).unwrap().version, 2);
+        world.register_template("Galleon", "Ship", &[cannons(40)]).unwrap();
+
+        let names: Vec<&str> = world.templates().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Galleon", "Sloop"]);
+
+        let (id, _) = world.spawn(&SpawnCommand::from_info("Sloop", 0, "Revenge", vec![]), "Blackbeard", UTC::now()).unwrap();
+        let ship = world.get(id).unwrap();
+        assert_eq!((ship.class.as_str(), ship.owner.as_str()), ("Ship", "Blackbeard"));
+        assert_eq!(ship.get_property("Cannons"), Some(&cannons(10)));
+
+        let (id, _) = world.spawn(&SpawnCommand::from_info("Sloop", 0, "", vec![cannons(12)]), "Blackbeard", UTC::now()).unwrap();
+        assert_eq!(world.get(id).unwrap().get_property("Cannons"), Some(&cannons(12)));
+
+        let mut stale = SpawnCommand::from_info("Sloop", 0, "", vec![]);
+        stale.template_version = 1;
+        assert_eq!(world.spawn(&stale, "Blackbeard", UTC::now()), Err(StoreError::StaleTemplate { name: String::from("Sloop"), expected: 1, current: 2 }));
+
+        let unknown = SpawnCommand::from_info("Brig", 0, "", vec![]);
+        assert_eq!(world.spawn(&unknown, "Blackbeard", UTC::now()), Err(StoreError::TemplateNotFound(String::from("Brig"))));
+    }
+
+    #[test]
+    fn test_visibility() {
+        let schema = |id: &str, visibility: Visibility| PropertySchema {
+            property_id: String::from(id),
+            name: String::new(),
+            value_type: ValueType::Int,
+            min_size: 0,
+            max_size: 0,
+            required: false,
+            index: IndexKind::None,
+            visibility
+        };
+        let int = |name: &str, value: i64| ObjProperties::typed(name, &PropertyValue::Int(value));
+        let names = |entry: &ObjectEntry| entry.properties.iter().map(|p| p.name.clone()).collect::<Vec<String>>();
+
+        let mut world = World::default();
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![
+            schema("Health", Visibility::Public),
+            schema("Cargo", Visibility::Owner),
+            schema("Orders", Visibility::Team)
+        ])).unwrap());
+
+        let add = PutObjCommand { class_name: String::from("Ship"), ..PutObjCommand::from_info(PutOperation::Add, 1, "Revenge", vec![int("Health", 100), int("Cargo", 40), int("Orders", 2)]) };
+        world.apply(&add, "Blackbeard", UTC::now()).unwrap();
+        world.add(2, "Island", &[int("Cargo", 500)]).unwrap();
+
+        let owner = Viewer { player: String::from("Blackbeard"), team: vec![], sees_all: false, delay: 0 };
+        let mate = Viewer { player: String::from("Calico Jack"), team: vec![String::from("Blackbeard"), String::from("Calico Jack")], sees_all: false, delay: 0 };
+        let enemy = Viewer { player: String::from("Anne Bonny"), team: vec![], sees_all: false, delay: 0 };
+
+        let snapshot = |viewer: &Viewer| names(&world.snapshot(None, viewer)[0]);
+        assert_eq!(snapshot(&owner), vec!["Health", "Cargo", "Orders"]);
+        assert_eq!(snapshot(&mate), vec!["Health", "Orders"]);
+        assert_eq!(snapshot(&enemy), vec!["Health"]);
+        assert_eq!(snapshot(&Viewer::everything()), vec!["Health", "Cargo", "Orders"]);
+
+        // Objects without a class are public.
+        assert_eq!(names(&world.snapshot(None, &enemy)[1]), vec!["Cargo"]);
+
+        // Hidden properties cannot be found through queries.
+        let query = QueryCommand::from_info(vec![QueryFilter::new(Comparison::Greater, int("Cargo", 10))]);
+        assert!(world.query(&query, &enemy).is_empty());
+        assert_eq!(world.query(&query, &owner).iter().map(|e| e.object_id).collect::<Vec<u64>>(), vec![1]);
+
+        // Pushed updates of hidden properties only are not sent.
+        let before = world.sequence();
+        world.update(1, &[int("Cargo", 35)], 0).unwrap();
+        let change = &world.change_set(before)[0];
+        assert_eq!(change.entry_for(None, &enemy), None);
+        assert_eq!(names(&change.entry_for(None, &owner).unwrap()), vec!["Cargo"]);
+
+        world.update(1, &[int("Health", 90), int("Orders", 3)], 0).unwrap();
+        let (changes, _) = world.changes_since(before, None, &enemy);
+        assert_eq!(names(&changes[0]), vec!["Health"]);
+    }
+
+    #[test]
+    fn test_hidden_position() {
+        let mut world = World::default();
+        world.register_class(ClassSchema::from_command(&ClassCommand::from_info("Submarine", vec![PropertySchema {
+            property_id: String::from(POSITION_PROPERTY),
+            name: String::new(),
+            value_type: ValueType::Vec2,
+            min_size: 0,
+            max_size: 0,
+            required: false,
+            index: IndexKind::None,
+            visibility: Visibility::Owner
+        }])).unwrap());
+
+        let add = PutObjCommand { class_name: String::from("Submarine"), ..PutObjCommand::from_info(PutOperation::Add, 1, "Nautilus", vec![position(10.0, 10.0)]) };
+        world.apply(&add, "Nemo", UTC::now()).unwrap();
+
+        let owner = Viewer { player: String::from("Nemo"), team: vec![], sees_all: false, delay: 0 };
+        let enemy = Viewer { player: String::from("Ned Land"), team: vec![], sees_all: false, delay: 0 };
+        let far = Area { min_x: 500.0, min_y: 500.0, max_x: 600.0, max_y: 600.0 };
+        let near = Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+
+        // Objects whose position is hidden are seen as objects without
+        // position, whatever the area.
+        assert!(world.snapshot(Some(&far), &owner).is_empty());
+        assert_eq!(world.snapshot(Some(&far), &enemy).len(), 1);
+        assert!(world.snapshot(Some(&near), &enemy)[0].properties.is_empty());
+        assert!(world.objects_in(&near, &enemy).is_empty());
+        assert!(world.positioned_objects(&enemy).is_empty());
+
+        // Moving out of the area is not told to viewers who cannot see it.
+        let before = world.sequence();
+        world.update(1, &[position(550.0, 550.0)], 0).unwrap();
+        let change = &world.change_set(before)[0];
+        assert_eq!(change.entry_for(Some(&near), &owner).unwrap().status, EntryStatus::Left);
+        assert_eq!(change.entry_for(Some(&near), &enemy), None);
+
+        let (changes, _) = world.changes_since(before, Some(&near), &enemy);
+        assert_eq!(changes[0].status, EntryStatus::Found);
+        assert!(changes[0].properties.is_empty());
+
+        // Nor is where it was deleted.
+        world.delete(1, 0).unwrap();
+        assert_eq!(world.changes_since(before, Some(&near), &enemy).0[0].status, EntryStatus::Deleted);
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let mut world = World::default();
+        world.add(7, "Island7", &[]).unwrap();
+        world.add(8, "Island8", &[]).unwrap();
+
+        let mut fort = PutObjCommand::from_info(PutOperation::Add, 1, "Fort", vec![]);
+        fort.parent_id = 7;
+        world.apply(&fort, "Blackbeard", UTC::now()).unwrap();
+
+        let mut cannon = PutObjCommand::from_info(PutOperation::Add, 2, "Cannon", vec![]);
+        cannon.parent_id = 1;
+        world.apply(&cannon, "Blackbeard", UTC::now()).unwrap();
+
+        let mut orphan = PutObjCommand::from_info(PutOperation::Add, 3, "Orphan", vec![]);
+        orphan.parent_id = 99;
+        assert_eq!(world.apply(&orphan, "Blackbeard", UTC::now()), Err(StoreError::InvalidParent { id: 3, parent: 99 }));
+        assert!(world.get(3).is_none());
+
+        assert_eq!(world.descendants(7), vec![1, 2]);
+        assert_eq!(world.get(2).unwrap().to_entry(&[]).parent_id, 1);
+
+        // An object cannot be moved under its own descendant.
+        assert_eq!(world.reparent(7, 2, 0).map(|o| o.id), Err(StoreError::InvalidParent { id: 7, parent: 2 }));
+
+        world.reparent(1, 8, 0).unwrap();
+        assert_eq!(world.get(1).unwrap().version, 2);
+        assert!(world.children(7).is_empty());
+
+        // A failed transaction leaves the tree as it was.
+        let mut moved = PutObjCommand::from_info(PutOperation::Add, 2, "Cannon", vec![]);
+        moved.parent_id = 7;
+        world.apply_all(&[moved, orphan], "Blackbeard", UTC::now()).unwrap_err();
+        assert_eq!(world.descendants(8), vec![1, 2]);
+        assert!(world.children(7).is_empty());
+
+        // Deleting the island deletes what is on it.
+        world.delete(8, 0).unwrap();
+        assert_eq!(world.len(), 1);
+        assert!(world.get(2).is_none());
+    }
+
+    #[test]
+    fn test_deep_hierarchy() {
+        let mut world = World::default();
+        world.add(1, "Root", &[]).unwrap();
+
+        for id in 2..=20_000 {
+            world.add(id, "", &[]).unwrap();
+            world.reparent(id, id - 1, 0).unwrap();
+        }
+
+        assert_eq!(world.descendants(1).len(), 19_999);
+        assert_eq!(world.reparent(1, 20_000, 0).map(|o| o.id), Err(StoreError::InvalidParent { id: 1, parent: 20_000 }));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut world = World::default();
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+
+        world.add(1, "Ship1", &[position(10.0, 10.0), prop("Health", b"100")]).unwrap();
+        world.add(2, "Ship2", &[position(500.0, 10.0)]).unwrap();
+        world.add(3, "Flag", &[]).unwrap();
+        world.update(1, &[prop("Health", b"90")], 0).unwrap();
+
+        let entries = world.snapshot(None, &Viewer::everything());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].properties.len(), 2);
+        assert_eq!(entries[0].version, 2);
+
+        let ids: Vec<u64> = world.snapshot(Some(&area), &Viewer::everything()).iter().map(|e| e.object_id).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut world = World::default();
+        world.set_history_duration(1000);
+        declare_class(&mut world, "Ship", &[(POSITION_PROPERTY, ValueType::Vec2), ("Flag", ValueType::Str)]);
+
+        let start = UTC::now();
+        let at = |ms: i64| start + chrono::Duration::milliseconds(ms);
+
+        let mut add = PutObjCommand::from_info(PutOperation::Add, 1, "Ship1", vec![position(0.0, 0.0), prop("Flag", b"Red")]);
+        add.class_name = String::from("Ship");
+        world.apply(&add, "Blackbeard", at(0)).unwrap();
+
+        let update = PutObjCommand::from_info(PutOperation::Update, 1, "", vec![position(10.0, 20.0), prop("Flag", b"Black")]);
+        world.apply(&update, "Blackbeard", at(100)).unwrap();
+
+        let names = [String::from("Position")];
+        let entry = world.state_at(1, at(30), RewindMode::Interpolate, &names, &Viewer::everything()).unwrap();
+        assert_eq!(entry.properties, vec![position(3.0, 6.0)]);
+        assert_eq!(entry.version, 1);
+
+        let entry = world.state_at(1, at(70), RewindMode::Nearest, &[], &Viewer::everything()).unwrap();
+        assert_eq!(entry.version, 2);
+        assert_eq!(entry.properties[1], prop("Flag", b"Black"));
+
+        // Non numeric properties take the value of the nearest state.
+        let entry = world.state_at(1, at(70), RewindMode::Interpolate, &[String::from("Flag")], &Viewer::everything()).unwrap();
+        assert_eq!(entry.properties, vec![prop("Flag", b"Black")]);
+        let entry = world.state_at(1, at(30), RewindMode::Interpolate, &[String::from("Flag")], &Viewer::everything()).unwrap();
+        assert_eq!(entry.properties, vec![prop("Flag", b"Red")]);
+
+        assert_eq!(world.state_at(1, at(-10), RewindMode::Nearest, &[], &Viewer::everything()), Err(StoreError::NoHistory(1)));
+        assert_eq!(world.state_at(1, at(5000), RewindMode::Nearest, &[], &Viewer::everything()).unwrap().version, 2);
+
+        // Properties added between two states are kept.
+        let mut add = PutObjCommand::from_info(PutOperation::Add, 2, "Ship2", vec![position(0.0, 0.0)]);
+        add.class_name = String::from("Ship");
+        world.apply(&add, "Blackbeard", at(0)).unwrap();
+        world.apply(&PutObjCommand::from_info(PutOperation::Update, 2, "", vec![position(10.0, 0.0), prop("Flag", b"Black")]), "Blackbeard", at(100)).unwrap();
+
+        let entry = world.state_at(2, at(30), RewindMode::Interpolate, &[], &Viewer::everything()).unwrap();
+        assert_eq!(entry.properties, vec![position(3.0, 0.0), prop("Flag", b"Black")]);
+
+        // States older than the history duration are dropped.
+        world.apply(&update, "Blackbeard", at(1200)).unwrap();
+        world.apply(&update, "Blackbeard", at(1300)).unwrap();
+        assert_eq!(world.get(1).unwrap().history.len(), 3);
+        assert!(world.state_at(1, at(50), RewindMode::Nearest, &[], &Viewer::everything()).is_err());
+
+        // The number of states is bounded, even with a huge duration.
+        world.set_history_duration(i64::MAX as u64);
+        world.apply(&update, "Blackbeard", at(1400)).unwrap();
+        world.set_history_duration(u64::MAX);
+
+        for ms in 0..(MAX_HISTORY_ENTRIES as i64 + 10) {
+            world.apply(&update, "Blackbeard", at(2000 + ms)).unwrap();
+        }
+
+        assert_eq!(world.get(1).unwrap().history.len(), MAX_HISTORY_ENTRIES);
+    }
+
+    #[test]
+    fn test_worlds_are_separate() {
+        let mut store = ObjectStore::new();
+
+        store.world_mut("Caribbean").add(1, "Ship", &[]).unwrap();
+        store.world_mut("Atlantic").add(1, "Ship", &[]).unwrap();
+
+        assert!(store.world("Pacific").is_none());
+        assert_eq!(store.world("Caribbean").unwrap().len(), 1);
+    }
+}
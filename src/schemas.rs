@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use crate::commands::{ClassCommand, IndexKind, ObjProperties, PropertySchema, PropertyValue, TemplateEntry, ValueType, Visibility};
+
+/**
+ * The properties objects of a class can have, registered by the host of a
+ * game session with a CLASS command.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassSchema {
+    pub name: String,
+    pub properties: Vec<PropertySchema>
+}
+
+impl ClassSchema {
+    /**
+     * Create a schema from a CLASS command. Property ids and logical names
+     * must be unique inside the class.
+     */
+    pub fn from_command(command: &ClassCommand) -> Result<ClassSchema, String> {
+        if command.name.is_empty() {
+            return Err(String::from("Class name cannot be empty"));
+        }
+
+        let mut names = HashSet::new();
+
+        for property in &command.properties {
+            if property.property_id.is_empty() {
+                return Err(format!("Property {} of class {} has no id", property.name, command.name));
+            }
+
+            if !names.insert(&property.property_id) || (!property.name.is_empty() && !names.insert(&property.name)) {
+                return Err(format!("Property {} is declared twice in class {}", property.property_id, command.name));
+            }
+
+            if property.max_size != 0 && property.min_size > property.max_size {
+                return Err(format!("Property {} of class {} has invalid size bounds", property.property_id, command.name));
+            }
+
+            let is_orderable = matches!(property.value_type, ValueType::Int | ValueType::Float | ValueType::Str);
+
+            if property.index == IndexKind::Ordered && !is_orderable {
+                return Err(format!("Property {} of class {} cannot have an ordered index", property.property_id, command.name));
+            }
+        }
+
+        Ok(ClassSchema {
+            name: command.name.clone(),
+            properties: command.properties.clone()
+        })
+    }
+
+    pub fn property(&self, property_id: &str) -> Option<&PropertySchema> {
+        self.properties.iter().find(|p| p.property_id == property_id)
+    }
+
+    /**
+     * Visibility of a property, properties the class does not declare are
+     * public.
+     */
+    pub fn visibility(&self, property_id: &str) -> Visibility {
+        self.property(property_id).map_or(Visibility::Public, |p| p.visibility)
+    }
+
+    /**
+     * Type of the data of a property, properties the class does not declare
+     * are raw data.
+     */
+    pub fn value_type(&self, property_id: &str) -> ValueType {
+        self.property(property_id).map_or(ValueType::Raw, |p| p.value_type)
+    }
+
+    /**
+     * Read the value of a property with the type the class declares.
+     */
+    pub fn value_of(&self, property: &ObjProperties) -> Result<PropertyValue, String> {
+        property.value_as(self.value_type(&property.name))
+    }
+
+    /**
+     * Find the compact id of a property from its logical name. Property ids
+     * are also accepted.
+     */
+    pub fn property_id(&self, name: &str) -> Option<&str> {
+        self.properties.iter()
+                       .find(|p| p.name == name || p.property_id == name)
+                       .map(|p| p.property_id.as_str())
+    }
+
+    /**
+     * Check properties sent by a client against the schema. Required
+     * properties are only checked when the object is created, updates only
+     * send the properties that changed.
+     */
+    pub fn validate(&self, properties: &[ObjProperties], is_new: bool) -> Result<(), String> {
+        for property in properties {
+            let schema = self.property(&property.name)
+                             .ok_or(format!("Class {} has no property {}", self.name, property.name))?;
+
+            if let Err(error) = property.value_as(schema.value_type) {
+                return Err(format!("Property {} of class {} must be {:?} : {}", property.name, self.name, schema.value_type, error));
+            }
+
+            let size = property.data.len() as u64;
+            if size < schema.min_size || (schema.max_size != 0 && size > schema.max_size) {
+                return Err(format!("Property {} of class {} has invalid size {}", property.name, self.name, size));
+            }
+        }
+
+        if is_new {
+            let missing = self.properties.iter()
+                                         .filter(|s| s.required)
+                                         .find(|s| !properties.iter().any(|p| p.name == s.property_id));
+
+            if let Some(schema) = missing {
+                return Err(format!("Property {} of class {} is required", schema.property_id, self.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Class and default properties of the objects spawned from a template,
+ * registered by the host of a game session with a TEMPLATE command.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectTemplate {
+    pub name: String,
+    pub version: u64,
+    pub class: String,
+    pub properties: Vec<ObjProperties>
+}
+
+impl ObjectTemplate {
+    /**
+     * The properties of an object spawned from the template, the overrides
+     * replacing the defaults with the same name.
+     */
+    pub fn properties_with(&self, overrides: &[ObjProperties]) -> Vec<ObjProperties> {
+        let mut properties: Vec<ObjProperties> = self.properties.iter()
+                                                     .filter(|p| !overrides.iter().any(|o| o.name == p.name))
+                                                     .cloned()
+                                                     .collect();
+        properties.extend_from_slice(overrides);
+
+        properties
+    }
+
+    pub fn to_entry(&self) -> TemplateEntry {
+        TemplateEntry {
+            name: self.name.clone(),
+            version: self.version,
+            class_name: self.class.clone(),
+            properties: self.properties.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::PropertyValue;
+
+    fn ship_class() -> ClassSchema {
+        let properties = vec![
+            PropertySchema {
+                property_id: String::from("Pos"),
+                name: String::from("Position"),
+                value_type: ValueType::Vec2,
+                min_size: 0,
+                max_size: 0,
+                required: true,
+                index: IndexKind::None,
+                visibility: Visibility::Public
+            },
+            PropertySchema {
+                property_id: String::from("Flag"),
+                name: String::from("FlagName"),
+                value_type: ValueType::Str,
+                min_size: 1,
+                max_size: 16,
+                required: false,
+                index: IndexKind::Hash,
+                visibility: Visibility::Team
+            }
+        ];
+
+        ClassSchema::from_command(&ClassCommand::from_info("Ship", properties)).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let class = ship_class();
+        let position = ObjProperties::typed("Pos", &PropertyValue::Vec2(1.0, 2.0));
+        let flag = ObjProperties::typed("Flag", &PropertyValue::Str(String::from("Jolly Roger")));
+
+        class.validate(&[position.clone(), flag.clone()], true).unwrap();
+        class.validate(std::slice::from_ref(&flag), false).unwrap();
+        class.validate(&[flag], true).expect_err("Position is required");
+
+        let wrong_type = ObjProperties::typed("Pos", &PropertyValue::Int(1));
+        class.validate(&[wrong_type], false).expect_err("Position is a Vec2");
+
+        let too_long = ObjProperties::typed("Flag", &PropertyValue::Str(String::from("The Queen Anne's Revenge")));
+        class.validate(&[too_long], false).expect_err("Flag is at most 16 bytes");
+
+        let unknown = ObjProperties::raw("Cargo", b"Rum");
+        class.validate(&[position, unknown], true).expect_err("Cargo is not declared");
+    }
+
+    #[test]
+    fn test_property_ids() {
+        let class = ship_class();
+
+        assert_eq!(class.property_id("Position"), Some("Pos"));
+        assert_eq!(class.property_id("Pos"), Some("Pos"));
+        assert_eq!(class.property_id("Cargo"), None);
+
+        assert_eq!(class.visibility("Flag"), Visibility::Team);
+        assert_eq!(class.visibility("Cargo"), Visibility::Public);
+
+        let duplicate = ClassCommand::from_info("Ship", vec![class.properties[0].clone(), class.properties[0].clone()]);
+        ClassSchema::from_command(&duplicate).expect_err("Property is declared twice");
+
+        let mut position = class.properties[0].clone();
+        position.index = IndexKind::Ordered;
+        ClassSchema::from_command(&ClassCommand::from_info("Ship", vec![position])).expect_err("Vec2 values cannot be ordered");
+    }
+
+    #[test]
+    fn test_template_overrides() {
+        let template = ObjectTemplate {
+            name: String::from("Sloop"),
+            version: 1,
+            class: String::from("Ship"),
+            properties: vec![ObjProperties::raw("Cannons", b"8"), ObjProperties::raw("Crew", b"20")]
+        };
+
+        let properties = template.properties_with(&[ObjProperties::raw("Crew", b"35"), ObjProperties::raw("Flag", b"Black")]);
+
+        assert_eq!(properties, vec![
+            ObjProperties::raw("Cannons", b"8"),
+            ObjProperties::raw("Crew", b"35"),
+            ObjProperties::raw("Flag", b"Black")
+        ]);
+    }
+}
@@ -1,9 +1,85 @@
 use chrono::prelude::*;
-use std::net::{TcpStream};
+use std::net::{TcpStream, IpAddr, Shutdown};
+use std::io::{Write};
 use std::sync::*;
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
 use std::borrow::*;
 use std::rc::*;
 use std::cell::*;
+use crate::spatial::Area;
+use crate::utils::frame_message;
+
+/**
+ * Object names a client wants to be notified about. Prefix subscriptions
+ * match every object whose name starts with the pattern.
+ */
+#[derive(PartialEq, Clone, Debug)]
+pub struct Subscription {
+    pub pattern: String,
+    pub prefix: bool
+}
+
+impl Subscription {
+    pub fn matches(&self, object_name: &str) -> bool {
+        if self.prefix {
+            object_name.starts_with(&self.pattern)
+        } else {
+            object_name == self.pattern
+        }
+    }
+}
+
+/**
+ * Message waiting in the outbox of a session, written once its time came.
+ */
+#[derive(PartialEq, Clone, Debug)]
+pub struct QueuedMessage {
+    pub send_at: Instant,
+    pub data: Vec<u8>
+}
+
+/**
+ * Write the messages received from an outbox until it closes or a write
+ * fails. Messages are written in the order they were queued, a message due
+ * now waits for the delayed messages queued before it.
+ */
+pub fn drain_outbox<F>(inbox: mpsc::Receiver<QueuedMessage>, mut write: F) -> Result<(), String>
+    where F: FnMut(&[u8]) -> Result<(), String> {
+
+    let mut pending: VecDeque<QueuedMessage> = VecDeque::new();
+
+    loop {
+        while pending.front().is_some_and(|m| m.send_at <= Instant::now()) {
+            let message = pending.pop_front().unwrap();
+
+            write(&message.data)?;
+        }
+
+        let received = match pending.front() {
+            Some(next) => {
+                let wait = next.send_at.saturating_duration_since(Instant::now());
+
+                match inbox.recv_timeout(wait) {
+                    Ok(message) => message,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    // Nothing more can be queued, finish writing what is pending.
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        std::thread::sleep(wait);
+
+                        continue;
+                    }
+                }
+            },
+            None => match inbox.recv() {
+                Ok(message) => message,
+                Err(_) => return Ok(())
+            }
+        };
+
+        pending.push_back(received);
+    }
+}
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum SessionState {
@@ -17,8 +93,19 @@ pub struct PlayerSession {
     pub state: SessionState,
     pub last_comm_time: chrono::DateTime<UTC>,
     pub player_socket: Option<Arc<Mutex<TcpStream>>>,
+    // Clone of the client socket outside of the lock held by the writer
+    // thread, so the connection can be closed while a write is blocked.
+    pub control_socket: Option<Arc<TcpStream>>,
+    // Address the client connected from, read when it was accepted.
+    pub address: Option<IpAddr>,
     pub messages_count: u32,
-    pub player_name: Option<String>
+    pub player_name: Option<String>,
+    pub game: Option<String>,
+    pub subscriptions: Vec<Subscription>,
+    // Area of the world the client receives objects from, everything if None.
+    pub interest: Option<Area>,
+    // Messages waiting to be written to the client by its writer thread.
+    pub outbox: Option<mpsc::Sender<QueuedMessage>>
 }
 
 impl PlayerSession {
@@ -29,6 +116,78 @@ impl PlayerSession {
     pub fn set_username(&mut self, username: String) {
         self.player_name = Some(username);
     }
+
+    /**
+     * Send a message to the client. When the session has an outbox the message
+     * is queued for the writer thread of the client so the caller never
+     * blocks on a slow client, otherwise it is written right away.
+     */
+    pub fn send_message(&self, data: &[u8]) -> Result<(), String> {
+        self.send_message_delayed(data, Duration::from_secs(0))
+    }
+
+    /**
+     * Queue a message to be sent to the client after a delay. Without an
+     * outbox the message can only be written right away.
+     */
+    pub fn send_message_delayed(&self, data: &[u8], delay: Duration) -> Result<(), String> {
+        let outbox = match &self.outbox {
+            Some(outbox) => outbox,
+            None if delay == Duration::from_secs(0) => return self.write_message(data),
+            None => return Err(String::from("Session has no outbox to delay messages"))
+        };
+
+        let message = QueuedMessage {
+            send_at: Instant::now().checked_add(delay).ok_or("Message delay is too long")?,
+            data: data.to_vec()
+        };
+
+        outbox.send(message).map_err(|_| String::from("Client writer thread has stopped"))
+    }
+
+    /**
+     * Write a message to the client socket. Messages are prefixed with their
+     * length, the same way the client frames its own messages.
+     */
+    pub fn write_message(&self, data: &[u8]) -> Result<(), String> {
+        let socket = self.player_socket.as_ref().ok_or("Session has no socket")?;
+        let mut socket = socket.lock().unwrap();
+
+        socket.write_all(&frame_message(data)).map_err(|e| format!("Unable to write to client : {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn subscribe(&mut self, subscription: Subscription) {
+        if !self.subscriptions.contains(&subscription) {
+            self.subscriptions.push(subscription);
+        }
+    }
+
+    pub fn unsubscribe(&mut self, subscription: &Subscription) {
+        self.subscriptions.retain(|s| s != subscription);
+    }
+
+    pub fn is_subscribed_to(&self, object_name: &str) -> bool {
+        self.subscriptions.iter().any(|s| s.matches(object_name))
+    }
+
+    /**
+     * Address the client is connecting from.
+     */
+    pub fn peer_address(&self) -> Option<IpAddr> {
+        self.address
+    }
+
+    /**
+     * Close the connection with the client. The client thread notices the
+     * connection was closed and exits, a write blocked on the client fails.
+     */
+    pub fn close(&self) {
+        if let Some(socket) = &self.control_socket {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
 }
 
 impl PartialEq for PlayerSession {
@@ -41,7 +200,7 @@ impl PartialEq for PlayerSession {
         let left = self.player_name.clone().unwrap();
         let right = other.player_name.clone().unwrap();
 
-        return left == right;
+        left == right
     }
 }
 
@@ -49,9 +208,15 @@ pub fn create_player_session(client_socket: TcpStream) -> PlayerSession {
     PlayerSession {
         state: SessionState::Closed,
         last_comm_time: chrono::UTC::now(),
+        control_socket: client_socket.try_clone().ok().map(Arc::new),
+        address: client_socket.peer_addr().ok().map(|addr| addr.ip()),
         player_socket: Some(Arc::new(Mutex::new(client_socket))),
         messages_count: 0,
-        player_name: None
+        player_name: None,
+        game: None,
+        subscriptions: vec![],
+        interest: None,
+        outbox: None
     }
 }
 
@@ -73,7 +238,7 @@ impl SessionManager {
      * Test method to try borrowing out of the list.
      */
     pub fn get_first_session(&mut self) -> &PlayerSession {
-        return self.sessions.first().unwrap();
+        self.sessions.first().unwrap()
     }
 
     /**
@@ -95,8 +260,7 @@ impl SessionManager {
 
         match res {
             Some((i, _)) => {
-                let mut dat = &self.sessions[i];
-                std::mem::replace(&mut dat, session);
+                self.sessions[i] = session.clone();
 
                 println!("Saved session {} with new info {:?}", i, &self.sessions[i]);
             },
@@ -106,6 +270,14 @@ impl SessionManager {
         }
     }
 
+    /**
+     * Get a copy of the session of a player.
+     */
+    pub fn get_session(&self, playername: &str) -> Option<PlayerSession> {
+        self.find_session(|item: &&PlayerSession| is_session_match(item, playername))
+            .map(|(_, session)| session)
+    }
+
     pub fn remove_session(&mut self, session: &PlayerSession) {
         if session.player_name.is_none() {
             return;
@@ -156,10 +328,10 @@ impl SessionManager {
 fn is_session_match(session: &PlayerSession, playername: &str) -> bool{
     match &session.player_name {
         Some(name) => {
-            return name == playername;
+            name == playername
         },
         None => {
-            return false;
+            false
         }
     }
 }
@@ -198,13 +370,87 @@ mod tests {
         assert_eq!(sesh.player_name.unwrap(), String::from("ObiWan"));
     }
 
+    #[test]
+    fn test_subscriptions() {
+        let mut sesh = create_test_session();
+
+        sesh.subscribe(Subscription { pattern: String::from("Ship"), prefix: true });
+        sesh.subscribe(Subscription { pattern: String::from("Island7"), prefix: false });
+        sesh.subscribe(Subscription { pattern: String::from("Island7"), prefix: false });
+        assert_eq!(sesh.subscriptions.len(), 2);
+
+        assert!(sesh.is_subscribed_to("Ship12"));
+        assert!(sesh.is_subscribed_to("Island7"));
+        assert!(!sesh.is_subscribed_to("Island77"));
+
+        sesh.unsubscribe(&Subscription { pattern: String::from("Ship"), prefix: true });
+        assert!(!sesh.is_subscribed_to("Ship12"));
+    }
+
+    #[test]
+    fn test_send_through_outbox() {
+        let mut sesh = create_test_session();
+        sesh.send_message(b"HELO").expect_err("No socket and no outbox");
+
+        let (tx, rx) = mpsc::channel();
+        sesh.outbox = Some(tx);
+
+        sesh.send_message(b"HELO").unwrap();
+        assert_eq!(rx.recv().unwrap().data, b"HELO");
+    }
+
+    #[test]
+    fn test_drain_outbox_in_order() {
+        let mut sesh = create_test_session();
+        let (tx, rx) = mpsc::channel();
+        sesh.outbox = Some(tx);
+
+        let start = Instant::now();
+        sesh.send_message_delayed(b"LATE", Duration::from_millis(50)).unwrap();
+        sesh.send_message(b"NOW").unwrap();
+        drop(sesh);
+
+        let mut written: Vec<Vec<u8>> = vec![];
+        drain_outbox(rx, |data| { written.push(data.to_vec()); Ok(()) }).unwrap();
+
+        // The message due now is not written before the one queued earlier.
+        assert_eq!(written, vec![b"LATE".to_vec(), b"NOW".to_vec()]);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_close_while_writing() {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (socket, _) = listener.accept().unwrap();
+
+        let sesh = create_player_session(socket);
+        assert_eq!(sesh.peer_address(), Some(client.local_addr().unwrap().ip()));
+
+        // The socket lock is held by the writer thread while it writes.
+        let _writing = sesh.player_socket.as_ref().unwrap().lock().unwrap();
+        sesh.close();
+
+        let mut buf = [0; 1];
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+    }
+
     fn create_test_session() -> PlayerSession {
         PlayerSession {
             state: SessionState::Closed,
             last_comm_time:chrono::UTC::now(),
             player_socket: None,
+            control_socket: None,
+            address: None,
             messages_count: 0,
-            player_name: None
+            player_name: None,
+            game: None,
+            subscriptions: vec![],
+            interest: None,
+            outbox: None
         }
     }
 }
\ No newline at end of file
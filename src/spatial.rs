@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+use crate::objects::ObjectId;
+
+// Size of the cells of the spatial grid, in world units.
+static CELL_SIZE: f64 = 64.0;
+
+pub type Position = (f64, f64);
+
+/**
+ * A rectangular area of the world, bounds included.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Area {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64
+}
+
+impl Area {
+    pub fn contains(&self, position: Position) -> bool {
+        let (x, y) = position;
+
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    /**
+     * Checks the bounds are finite and the minimum is not above the maximum.
+     */
+    pub fn is_valid(&self) -> bool {
+        let finite = [self.min_x, self.min_y, self.max_x, self.max_y].iter().all(|v| v.is_finite());
+
+        finite && self.min_x <= self.max_x && self.min_y <= self.max_y
+    }
+}
+
+/**
+ * Index of object positions. The world is split in square cells so finding
+ * the objects inside an area only looks at the cells covering it.
+ */
+#[derive(Clone, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<(i64, i64), HashSet<ObjectId>>,
+    positions: HashMap<ObjectId, Position>
+}
+
+impl SpatialGrid {
+    /**
+     * Set the position of an object, moving it to another cell if needed.
+     */
+    pub fn insert(&mut self, id: ObjectId, position: Position) {
+        self.remove(id);
+
+        self.cells.entry(cell_of(position)).or_default().insert(id);
+        self.positions.insert(id, position);
+    }
+
+    pub fn remove(&mut self, id: ObjectId) {
+        if let Some(position) = self.positions.remove(&id) {
+            let cell = cell_of(position);
+
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.remove(&id);
+
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    pub fn position_of(&self, id: ObjectId) -> Option<Position> {
+        self.positions.get(&id).cloned()
+    }
+
+    /**
+     * List the objects inside an area, sorted by id. An area covering more
+     * cells than there are objects is answered by looking at every object
+     * instead.
+     */
+    pub fn query(&self, area: &Area) -> Vec<ObjectId> {
+        if !area.is_valid() {
+            return vec![];
+        }
+
+        let (min_cx, min_cy) = cell_of((area.min_x, area.min_y));
+        let (max_cx, max_cy) = cell_of((area.max_x, area.max_y));
+
+        let cell_count = (max_cx as i128 - min_cx as i128 + 1).saturating_mul(max_cy as i128 - min_cy as i128 + 1);
+
+        let mut result: Vec<ObjectId> = if cell_count > self.positions.len() as i128 {
+            self.positions.iter().filter(|(_, p)| area.contains(**p)).map(|(id, _)| *id).collect()
+        } else {
+            let mut found = vec![];
+
+            for cx in min_cx..=max_cx {
+                for cy in min_cy..=max_cy {
+                    if let Some(ids) = self.cells.get(&(cx, cy)) {
+                        found.extend(ids.iter().filter(|id| area.contains(self.positions[id])));
+                    }
+                }
+            }
+
+            found
+        };
+
+        result.sort_unstable();
+
+        result
+    }
+}
+
+fn cell_of(position: Position) -> (i64, i64) {
+    let (x, y) = position;
+
+    ((x / CELL_SIZE).floor() as i64, (y / CELL_SIZE).floor() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_contains() {
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 10.0, max_y: 10.0 };
+
+        assert!(area.contains((0.0, 10.0)));
+        assert!(!area.contains((-0.1, 5.0)));
+        assert!(!Area { min_x: 1.0, min_y: 0.0, max_x: 0.0, max_y: 0.0 }.is_valid());
+        assert!(!Area { min_x: f64::NEG_INFINITY, min_y: 0.0, max_x: 0.0, max_y: 0.0 }.is_valid());
+        assert!(!Area { min_x: 0.0, min_y: 0.0, max_x: f64::NAN, max_y: 0.0 }.is_valid());
+    }
+
+    #[test]
+    fn test_grid_query() {
+        let mut grid = SpatialGrid::default();
+
+        grid.insert(1, (10.0, 10.0));
+        grid.insert(2, (100.0, 100.0));
+        grid.insert(3, (-500.0, 20.0));
+
+        let area = Area { min_x: 0.0, min_y: 0.0, max_x: 200.0, max_y: 200.0 };
+        assert_eq!(grid.query(&area), vec![1, 2]);
+
+        // Moving an object moves it between cells.
+        grid.insert(2, (-490.0, 25.0));
+        assert_eq!(grid.query(&area), vec![1]);
+
+        let west = Area { min_x: -600.0, min_y: 0.0, max_x: -400.0, max_y: 100.0 };
+        assert_eq!(grid.query(&west), vec![2, 3]);
+
+        grid.remove(3);
+        assert_eq!(grid.query(&west), vec![2]);
+        assert_eq!(grid.position_of(3), None);
+
+        // Huge areas do not walk their cells.
+        let world = Area { min_x: -1e300, min_y: -1e300, max_x: 1e300, max_y: 1e300 };
+        assert_eq!(grid.query(&world), vec![1, 2]);
+
+        let wide = Area { min_x: 0.0, min_y: 0.0, max_x: 1e7, max_y: 1e7 };
+        assert_eq!(grid.query(&wide), vec![1]);
+    }
+}
@@ -2,7 +2,7 @@ use std::net::{TcpStream};
 use std::io::{Write};
 use crate::utils::*;
 
-/**
+/*
  * Public functions in this file are used to start client threads
  * that acts in a particular way. Helpful for testing server implementation.
  */
@@ -16,7 +16,7 @@ pub fn start_client_idle() {
         let mut conn = connect_local();
 
         loop {
-            conn.write_all(b"HELO_OYYY").unwrap();
+            conn.write_all(&frame_message(b"HELO_OYYY")).unwrap();
 
             std::thread::sleep(std::time::Duration::from_millis(5000));
         }
@@ -42,8 +42,8 @@ pub fn start_client_bad_hello_sequence() {
     std::thread::spawn(move || {
         let mut conn = connect_local();
 
-        conn.write_all(b"HELO_NBONE|").unwrap();
-        conn.write_all(b"HELO_NBTWO|").unwrap();
+        conn.write_all(&frame_message(b"HELO_NBONE")).unwrap();
+        conn.write_all(&frame_message(b"HELO_NBTWO")).unwrap();
     });
 }
 
@@ -61,8 +61,8 @@ pub fn start_client_start_stop() {
         cmd.append(&mut two);
         cmd.append(&mut three);
 
-        conn.write_all(&cmd).unwrap();
-        conn.write_all(b"BYYE|").unwrap();
+        conn.write_all(&frame_message(&cmd)).unwrap();
+        conn.write_all(&frame_message(b"BYYE")).unwrap();
     });
 }
 
@@ -70,5 +70,5 @@ pub fn start_client_start_stop() {
  * Private utility function to create a connection to the server.
  */
 fn connect_local() -> std::net::TcpStream {
-    return TcpStream::connect("localhost:5555").unwrap();
+    TcpStream::connect("localhost:5555").unwrap()
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 extern crate byteorder;
 use byteorder::*;
-use std::io::{BufReader, Cursor};
+use std::io::{BufReader, Cursor, Read};
 use std::convert::TryInto;
 
 /**
@@ -32,7 +32,7 @@ pub fn get_messageblocks_groups(data: &[u8]) -> std::result::Result<Vec<Vec<u8>>
         current_index += 1;
     }
 
-    return Ok(result_vector);
+    Ok(result_vector)
 }
 
 /**
@@ -49,7 +49,7 @@ pub fn pad_string(buf: &[u8], padlen: usize) -> Vec<u8> {
         }
     }
 
-    return result;
+    result
 }
 
 pub fn trim_vec_end(buf: &[u8]) -> Vec<u8> {
@@ -57,7 +57,7 @@ pub fn trim_vec_end(buf: &[u8]) -> Vec<u8> {
 
     let mut data_started = false;
     for i in (0..buf.len()).rev() {
-        if data_started == true {
+        if data_started {
             res.push(buf[i]);
         } else if buf[i] != 0 {
             res.push(buf[i]);
@@ -67,7 +67,7 @@ pub fn trim_vec_end(buf: &[u8]) -> Vec<u8> {
 
     res.reverse();
 
-    return res;
+    res
 }
 
 pub fn vec_to_trimmed_string(buf: &[u8]) -> Result<String, std::string::FromUtf8Error> {
@@ -77,9 +77,7 @@ pub fn vec_to_trimmed_string(buf: &[u8]) -> Result<String, std::string::FromUtf8
 pub fn buf_to_u32(buf: [u8; 4]) -> u32 {
     let mut rdr = Cursor::new(buf);
 
-    let x = rdr.read_u32::<LittleEndian>().unwrap();
-
-    return x;
+    rdr.read_u32::<LittleEndian>().unwrap()
 }
 
 pub fn u32_to_buf(nb: u32) -> Vec<u8> {
@@ -87,15 +85,13 @@ pub fn u32_to_buf(nb: u32) -> Vec<u8> {
 
     res.write_u32::<LittleEndian>(nb).unwrap();
 
-    return res;
+    res
 }
 
 pub fn buf_to_u64(buf: [u8; 8]) -> u64 {
     let mut rdr = Cursor::new(buf);
 
-    let x = rdr.read_u64::<LittleEndian>().unwrap();
-
-    return x;
+    rdr.read_u64::<LittleEndian>().unwrap()
 }
 
 pub fn u64_to_buf(nb: u64) -> Vec<u8> {
@@ -103,15 +99,113 @@ pub fn u64_to_buf(nb: u64) -> Vec<u8> {
 
     res.write_u64::<LittleEndian>(nb).unwrap();
 
-    return res;
+    res
+}
+
+/**
+ * Largest message accepted from a client, in bytes.
+ */
+pub static MAX_MESSAGE_LENGTH: u32 = 16 * 1024 * 1024;
+
+/**
+ * Prefix a message with its length so it can be written to a socket.
+ *
+ * Wire format : [length:4][data]
+ */
+pub fn frame_message(data: &[u8]) -> Vec<u8> {
+    let mut result = u32_to_buf(data.len() as u32);
+    result.extend_from_slice(data);
+
+    result
+}
+
+/**
+ * Read one message written by frame_message. Fails when the stream ends or
+ * the message is longer than MAX_MESSAGE_LENGTH.
+ */
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).map_err(|e| format!("Unable to read message length : {}", e))?;
+
+    let length = buf_to_u32(length);
+
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(format!("Message of {} bytes is too long", length));
+    }
+
+    let mut data = vec![0; length as usize];
+    reader.read_exact(&mut data).map_err(|e| format!("Unable to read message : {}", e))?;
+
+    Ok(data)
+}
+
+/**
+ * Read a fixed length field from a reader and return it as a string with the
+ * padding bytes trimmed.
+ */
+pub fn read_padded_string<R: Read>(reader: &mut R, len: usize) -> Result<String, String> {
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).map_err(|_| "Unable to read string field from buffer.")?;
+
+    vec_to_trimmed_string(&buf).map_err(|_| String::from("String field is not UTF8."))
+}
+
+/**
+ * Read an 8 bytes little endian field from a reader.
+ */
+pub fn read_u64_field<R: Read>(reader: &mut R) -> Result<u64, String> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf).map_err(|_| "Unable to read number field from buffer.")?;
+
+    Ok(buf_to_u64(buf))
+}
+
+/**
+ * Read an 8 bytes little endian floating point field from a reader.
+ */
+pub fn read_f64_field<R: Read>(reader: &mut R) -> Result<f64, String> {
+    Ok(f64::from_bits(read_u64_field(reader)?))
+}
+
+pub fn f64_to_buf(nb: f64) -> Vec<u8> {
+    u64_to_buf(nb.to_bits())
+}
+
+/**
+ * Read a list of fixed length strings. The list is prepended by its number
+ * of items, encoded on 8 bytes.
+ */
+pub fn read_string_list<R: Read>(reader: &mut R, item_len: usize) -> Result<Vec<String>, String> {
+    let count = read_u64_field(reader)?;
+
+    let mut result = vec![];
+    for _i in 0..count {
+        result.push(read_padded_string(reader, item_len)?);
+    }
+
+    Ok(result)
+}
+
+/**
+ * Serialize a list of strings, each padded to item_len bytes, prepended by the
+ * number of items.
+ */
+pub fn string_list_to_buf(list: &[String], item_len: usize) -> Vec<u8> {
+    let mut result = u64_to_buf(list.len() as u64);
+
+    for item in list {
+        result.append(&mut pad_string(item.as_bytes(), item_len));
+    }
+
+    result
 }
 
 pub fn u32_to_usize(nb: u32) -> usize {
-    return nb.try_into().unwrap();
+    nb.try_into().unwrap()
 }
 
 pub fn usize_to_u32(size: usize) -> u32 {
-    return size.try_into().unwrap();
+    size.try_into().unwrap()
 }
 
 /**
@@ -119,7 +213,7 @@ pub fn usize_to_u32(size: usize) -> u32 {
  * otherwise.
  */
 pub fn result_from_condition<T>(condition: bool, errordata: T) -> Result<(), T> {
-    if condition == false {
+    if !condition {
         return Err(errordata);
     }
 
@@ -133,14 +227,51 @@ pub fn result_from_condition<T>(condition: bool, errordata: T) -> Result<(), T>
 pub fn result_from_predicate<F, T>(predicate: F, errordata: T) -> Result<(), T>
     where F: Fn() -> bool
 {
-    if predicate() == false {
+    if !predicate() {
         return Err(errordata);
     }
 
     Ok(())
 }
 
+/**
+ * Checks if a text matches a glob pattern, where `*` matches any sequence of
+ * characters and `?` matches a single character. An empty pattern matches
+ * everything.
+ */
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    if pattern.is_empty() {
+        return true;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position of the last star in the pattern and of the text it resumes at.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -209,6 +340,35 @@ mod tests {
         assert_eq!(buf, [0xDD, 0xCC, 0xBB, 0xAA]);
     }
 
+    #[test]
+    fn test_read_fields() {
+        let mut data = pad_string(b"Field", 16);
+        data.append(&mut u64_to_buf(0xAABB));
+
+        let mut reader = BufReader::new(data.as_slice());
+
+        assert_eq!(read_padded_string(&mut reader, 16).unwrap(), "Field");
+        assert_eq!(read_u64_field(&mut reader).unwrap(), 0xAABB);
+
+        // Nothing left to read
+        read_u64_field(&mut reader).expect_err("Buffer is empty");
+
+        let data = f64_to_buf(-12.5);
+        let mut reader = BufReader::new(data.as_slice());
+        assert_eq!(read_f64_field(&mut reader).unwrap(), -12.5);
+    }
+
+    #[test]
+    fn test_string_list() {
+        let list = vec![String::from("One"), String::from("Two")];
+
+        let data = string_list_to_buf(&list, 32);
+        assert_eq!(data.len(), 8 + 64);
+
+        let mut reader = BufReader::new(data.as_slice());
+        assert_eq!(read_string_list(&mut reader, 32).unwrap(), list);
+    }
+
     #[test]
     fn test_u32_to_usize() {
         // The test is using the same method to compare the usize as u32
@@ -261,4 +421,31 @@ mod tests {
         let ok_test = result_from_predicate(|| true, "GOT ERROR");
         assert_eq!(ok_test.is_ok(), true);
     }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("", "Ship1"));
+        assert!(glob_matches("Ship*", "Ship12"));
+        assert!(glob_matches("Ship?", "Ship1"));
+        assert!(!glob_matches("Ship?", "Ship12"));
+        assert!(glob_matches("*Pearl*", "Black Pearl II"));
+        assert!(glob_matches("I*d*7", "Island7"));
+        assert!(!glob_matches("Island", "Island7"));
+        assert!(!glob_matches("Ship*", "Island"));
+    }
+
+    #[test]
+    fn test_frames() {
+        // Messages may contain any byte, including the old '|' separator.
+        let framed: Vec<u8> = [frame_message(b"PUTOBJ|1"), frame_message(b"")].concat();
+        let mut reader = Cursor::new(framed);
+
+        assert_eq!(read_frame(&mut reader).unwrap(), b"PUTOBJ|1");
+        assert_eq!(read_frame(&mut reader).unwrap(), b"");
+        read_frame(&mut reader).expect_err("Stream ended");
+
+        let truncated = frame_message(b"PUTOBJ");
+        read_frame(&mut Cursor::new(&truncated[0..truncated.len() - 1])).expect_err("Message is truncated");
+        read_frame(&mut Cursor::new(u32_to_buf(MAX_MESSAGE_LENGTH + 1))).expect_err("Message is too long");
+    }
 }
\ No newline at end of file